@@ -0,0 +1,367 @@
+use crate::config::{Config, ScoreChanges};
+use crate::error::Error;
+use crate::get::{Hole, Score, ShortTournament, Tournament, ViewHolePage};
+use crate::state::Store;
+use reqwest::blocking as http;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/**
+ * Where the tournaments and scores come from, and where scores are submitted to
+ *
+ * The names and shapes are the ones of the API server, see
+ * <https://github.com/itzgoldenleonard/BullseyeGolf-server/blob/main/openapi.yaml>
+ */
+pub trait Backend {
+    /// Every tournament `user` has, without the holes
+    fn tournaments(&self, user: &str) -> Result<Vec<ShortTournament>, Error>;
+    /// The tournament with every hole and score
+    fn tournament(&self, user: &str, tournament: &str) -> Result<Tournament, Error>;
+    fn hole(&self, hole: &ViewHolePage) -> Result<Hole, Error>;
+    /// Adds `score` to `hole`
+    fn submit(&self, hole: &ViewHolePage, score: &Score) -> Result<(), Error>;
+    /// Removes `score` from `hole`, it has to be exactly like it was submitted
+    fn delete(&self, hole: &ViewHolePage, score: &Score) -> Result<(), Error>;
+    /// Whether [`Backend::delete`] can be used, so players can change their scores
+    fn can_delete(&self) -> bool;
+}
+
+/**
+ * The backend `server` (the value of `SERVER_URL`) points to
+ *
+ * `file://<path>` is a JSON file read by [`JsonFile`], anything else is the URL of the API server
+ */
+pub fn open(server: &str, config: &Config) -> Result<Box<dyn Backend>, Error> {
+    match server.strip_prefix("file://") {
+        Some(path) => Ok(Box::new(JsonFile::open(path)?)),
+        None => Ok(Box::new(Api {
+            server: server.trim_end_matches('/').to_owned(),
+            cache_seconds: config.cache_seconds,
+            state: config.state.clone(),
+            score_changes: config.score_changes,
+        })),
+    }
+}
+
+/**
+ * The BullseyeGolf API server at `server`
+ *
+ * The responses are cached for `cache_seconds`, see [`crate::config::Config::cache_seconds`]
+ */
+struct Api {
+    server: String,
+    cache_seconds: u64,
+    /// Where the responses are cached
+    state: Store,
+    score_changes: ScoreChanges,
+}
+
+/// The namespace of the cached responses in the [`Store`], by URL, see [`Store::cached`]
+const CACHE: &str = "api_cache";
+
+impl Api {
+    fn tournament_url(&self, user: &str, tournament: &str) -> String {
+        format!("{}/{user}/{tournament}", self.server)
+    }
+
+    fn hole_url(&self, hole: &ViewHolePage) -> String {
+        format!(
+            "{}/{}/{}/{}",
+            self.server, hole.user, hole.tournament, hole.hole
+        )
+    }
+
+    /**
+     * The response to `request` for `url`, or the one from less than `cache_seconds` ago
+     *
+     * The cache is kept in the [`Store`], on disk in CGI mode and in memory in the resident modes.
+     * Only successful responses with valid JSON are cached. If the cache can't be used that's
+     * logged and the API server is asked directly.
+     */
+    fn get<T: DeserializeOwned>(
+        &self,
+        url: String,
+        request: impl FnOnce(http::RequestBuilder) -> http::RequestBuilder,
+    ) -> Result<T, Error> {
+        let request = request(client().get(&url));
+        if self.cache_seconds == 0 {
+            return Ok(request.send()?.error_for_status()?.json()?);
+        }
+        match self.state.cached(CACHE, &url, self.cache_seconds) {
+            Ok(Some(body)) => {
+                if let Ok(response) = serde_json::from_str(&body) {
+                    return Ok(response);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Could not read the cached response from {url}: {e:?}"),
+        }
+        let body = request.send()?.error_for_status()?.text()?;
+        let response = serde_json::from_str(&body)?;
+        if let Err(e) = self.state.cache(CACHE, &url, &body, self.cache_seconds) {
+            eprintln!("Could not cache the response from {url}: {e:?}");
+        }
+        Ok(response)
+    }
+
+    /**
+     * Makes the next requests for `hole` and its tournament go to the API server
+     *
+     * Failing to is only logged, since the score has been changed on the API server already
+     */
+    fn invalidate(&self, hole: &ViewHolePage) {
+        if self.cache_seconds == 0 {
+            return;
+        }
+        for url in [
+            self.hole_url(hole),
+            self.tournament_url(&hole.user, &hole.tournament),
+        ] {
+            if let Err(e) = self.state.uncache(CACHE, &url) {
+                eprintln!("Could not clear the cached response from {url}: {e:?}");
+            }
+        }
+    }
+}
+
+impl Backend for Api {
+    fn tournaments(&self, user: &str) -> Result<Vec<ShortTournament>, Error> {
+        self.get(format!("{}/{user}", self.server), |request| request)
+    }
+
+    fn tournament(&self, user: &str, tournament: &str) -> Result<Tournament, Error> {
+        self.get(self.tournament_url(user, tournament), |request| {
+            request.header("No-Hole-Images", "true")
+        })
+    }
+
+    fn hole(&self, hole: &ViewHolePage) -> Result<Hole, Error> {
+        self.get(self.hole_url(hole), |request| request)
+    }
+
+    fn submit(&self, hole: &ViewHolePage, score: &Score) -> Result<(), Error> {
+        client()
+            .post(self.hole_url(hole))
+            .json(score)
+            .send()?
+            .error_for_status()?;
+        self.invalidate(hole);
+        Ok(())
+    }
+
+    /**
+     * Assumes the API server has a `DELETE` for the hole that takes the score in the body, like
+     * when it's submitted. That route isn't in the API's OpenAPI spec, so it's only used with
+     * `SCORE_CHANGES=on`
+     */
+    fn delete(&self, hole: &ViewHolePage, score: &Score) -> Result<(), Error> {
+        client()
+            .delete(self.hole_url(hole))
+            .json(score)
+            .send()?
+            .error_for_status()?;
+        self.invalidate(hole);
+        Ok(())
+    }
+
+    fn can_delete(&self) -> bool {
+        self.score_changes == ScoreChanges::On
+    }
+}
+
+/**
+ * The HTTP client used for every request to the API server
+ *
+ * It is shared so that connections (and TLS sessions) are reused when the program stays
+ * resident between requests
+ */
+fn client() -> &'static http::Client {
+    static CLIENT: OnceLock<http::Client> = OnceLock::new();
+    CLIENT.get_or_init(http::Client::new)
+}
+
+/**
+ * Tournaments kept in a JSON file instead of on an API server, for demos without a connection
+ *
+ * The file has the tournaments by id, by user, e.g.
+ *
+ * ```json
+ * { "<user>": { "<tournament id>": {
+ *     "tournament_name": "Summer Cup", "tournament_sponsor": "", "active": true,
+ *     "t_start": 1700000000, "t_end": 4102444800,
+ *     "holes": [{ "hole_number": 1, "hole_text": "", "hole_sponsor": "", "scores": [] }]
+ * } } }
+ * ```
+ *
+ * Submitted and withdrawn scores are written to a temporary file that then replaces the file,
+ * so it's never seen half written. Writers take turns by locking `<path>.lock`, so concurrent
+ * CGI processes don't lose scores.
+ */
+pub struct JsonFile {
+    path: PathBuf,
+    /// Used instead of the file, so tests can render pages without files, see
+    /// [`JsonFile::from_value`]
+    #[cfg(test)]
+    memory: Option<std::sync::Mutex<Value>>,
+}
+
+impl JsonFile {
+    fn open(path: &str) -> Result<Self, Error> {
+        let path = PathBuf::from(path);
+        if !path.is_file() {
+            return Err(critical(path.display(), "no such file"));
+        }
+        Ok(Self {
+            path,
+            #[cfg(test)]
+            memory: None,
+        })
+    }
+
+    /// The tournaments in `data`, in the format of the file, only kept in memory
+    #[cfg(test)]
+    pub fn from_value(data: Value) -> Self {
+        Self {
+            path: PathBuf::new(),
+            memory: Some(data.into()),
+        }
+    }
+
+    /**
+     * Runs `f` on the content of the file, [`Error::NotFound`] if it returns [`None`]
+     *
+     * The file is only written if `write` is set and `f` found what it was looking for. Reading
+     * needs no lock, since the file is only ever replaced as a whole
+     */
+    fn with_data<T>(
+        &self,
+        write: bool,
+        f: impl FnOnce(&mut Value) -> Option<T>,
+    ) -> Result<T, Error> {
+        #[cfg(test)]
+        if let Some(data) = &self.memory {
+            return f(&mut data.lock().unwrap()).ok_or(Error::NotFound);
+        }
+        let context = self.path.display();
+        let lock = match write {
+            true => {
+                let lock =
+                    File::create(self.sibling(".lock")).map_err(|e| critical(&context, e))?;
+                lock.lock().map_err(|e| critical(&context, e))?;
+                Some(lock)
+            }
+            false => None,
+        };
+        let content = fs::read_to_string(&self.path).map_err(|e| critical(&context, e))?;
+        let mut data = serde_json::from_str(&content).map_err(|e| critical(&context, e))?;
+
+        let result = f(&mut data).ok_or(Error::NotFound)?;
+        if write {
+            let temporary = self.sibling(".tmp");
+            fs::write(&temporary, serde_json::to_string_pretty(&data)?)?;
+            fs::set_permissions(&temporary, fs::metadata(&self.path)?.permissions())?;
+            fs::rename(&temporary, &self.path)?;
+        }
+        drop(lock);
+        Ok(result)
+    }
+
+    /// The path of the file with `suffix` appended, in the same directory so it can be renamed
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(suffix);
+        path.into()
+    }
+
+    /// Changes the scores of `hole` with `f` and writes them to the file
+    fn scores<T>(
+        &self,
+        hole: &ViewHolePage,
+        f: impl FnOnce(&mut Vec<Value>) -> Option<T>,
+    ) -> Result<T, Error> {
+        self.with_data(true, |data| {
+            let scores = data
+                .get_mut(&hole.user)?
+                .get_mut(&hole.tournament)?
+                .get_mut("holes")?
+                .as_array_mut()?
+                .iter_mut()
+                .find(|h| h["hole_number"].as_u64() == Some(hole.hole.into()))?
+                .get_mut("scores")?
+                .as_array_mut()?;
+            f(scores)
+        })
+    }
+}
+
+impl Backend for JsonFile {
+    fn tournaments(&self, user: &str) -> Result<Vec<ShortTournament>, Error> {
+        let tournaments = self.with_data(false, |data| {
+            let tournaments = data.get(user)?.as_object()?.iter().map(|(id, t)| {
+                json!({
+                    "tournament_id": id,
+                    "tournament_name": t["tournament_name"],
+                    "active": t["active"],
+                    "t_start": t["t_start"],
+                    "t_end": t["t_end"],
+                })
+            });
+            Some(Value::Array(tournaments.collect()))
+        })?;
+        parse(tournaments)
+    }
+
+    fn tournament(&self, user: &str, tournament: &str) -> Result<Tournament, Error> {
+        parse(self.with_data(false, |data| data.get(user)?.get(tournament).cloned())?)
+    }
+
+    fn hole(&self, hole: &ViewHolePage) -> Result<Hole, Error> {
+        let number = u64::from(hole.hole);
+        parse(self.with_data(false, |data| {
+            data.get(&hole.user)?
+                .get(&hole.tournament)?
+                .get("holes")?
+                .as_array()?
+                .iter()
+                .find(|h| h["hole_number"].as_u64() == Some(number))
+                .cloned()
+        })?)
+    }
+
+    /// Keeps the scores ordered from best to worst, like the API server
+    fn submit(&self, hole: &ViewHolePage, score: &Score) -> Result<(), Error> {
+        let value = serde_json::to_value(score)?;
+        self.scores(hole, |scores| {
+            let i = scores.partition_point(|s| {
+                s["player_score"].as_f64().unwrap_or_default() <= score.player_score
+            });
+            scores.insert(i, value);
+            Some(())
+        })
+    }
+
+    fn delete(&self, hole: &ViewHolePage, score: &Score) -> Result<(), Error> {
+        let value = serde_json::to_value(score)?;
+        self.scores(hole, |scores| {
+            let i = scores.iter().position(|s| *s == value)?;
+            scores.remove(i);
+            Some(())
+        })
+    }
+
+    fn can_delete(&self) -> bool {
+        true
+    }
+}
+
+fn parse<T: DeserializeOwned>(value: Value) -> Result<T, Error> {
+    Ok(serde_json::from_value(value)?)
+}
+
+fn critical(context: impl Display, error: impl Display) -> Error {
+    Error::CriticalServer(format!("{context}: {error}").into())
+}
@@ -0,0 +1,314 @@
+use crate::csrf::Secret;
+use crate::error::Error;
+use crate::state::Store;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+
+/**
+ * All the settings of the deployment
+ *
+ * Read from the environment once when the program starts, so the resident modes don't read
+ * `TOURNAMENT_CONFIG` again for every request, and a mistake in the settings is found before
+ * the first request
+ */
+#[derive(Debug)]
+pub struct Config {
+    /// For tournaments without overrides
+    limits: Limits,
+    /// The overrides from `TOURNAMENT_CONFIG`, by tournament id
+    tournaments: HashMap<String, Overrides>,
+    pub rate_limit: RateLimit,
+    pub duplicates: Duplicates,
+    pub member_numbers: MemberNumbers,
+    pub score_changes: ScoreChanges,
+    /// See [`cache_seconds`]
+    pub cache_seconds: u64,
+    /// `STATE_DIR`
+    pub state: Store,
+    /// `CSRF_SECRET`
+    pub secret: Secret,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, Error> {
+        let mut limits = Limits::default();
+        if let Some(max_meters) = var("MAX_METERS")? {
+            limits.max_meters = max_meters;
+        }
+        if let Some(resolution_cm) = var("RESOLUTION_CM")? {
+            limits.resolution_cm = resolution_cm;
+        }
+        limits.validate("RESOLUTION_CM")?;
+
+        let tournaments: HashMap<String, Overrides> = match env::var("TOURNAMENT_CONFIG") {
+            Ok(path) => {
+                let file = fs::read_to_string(&path).map_err(|e| critical(&path, e))?;
+                serde_json::from_str(&file).map_err(|e| critical(&path, e))?
+            }
+            Err(_) => HashMap::new(),
+        };
+        for (tournament, overrides) in &tournaments {
+            overrides
+                .apply(limits)
+                .validate(&format!("resolution_cm of {tournament}"))?;
+        }
+
+        let state = Store::from_env();
+        Ok(Self {
+            limits,
+            tournaments,
+            rate_limit: RateLimit::from_env()?,
+            duplicates: Duplicates::from_env()?,
+            member_numbers: MemberNumbers::from_env()?,
+            score_changes: ScoreChanges::from_env()?,
+            cache_seconds: cache_seconds()?,
+            secret: Secret::from_env(state.clone()),
+            state,
+        })
+    }
+
+    /// The same settings, with the state and the generated secret in `dir` instead
+    #[cfg(test)]
+    pub fn with_state_dir(self, dir: impl Into<std::path::PathBuf>) -> Self {
+        let state = Store::new(dir);
+        Self {
+            secret: Secret::from_env(state.clone()),
+            state,
+            ..self
+        }
+    }
+
+    /// The limits for `tournament`, or for the deployment if there is no tournament
+    pub fn limits(&self, tournament: Option<&str>) -> Limits {
+        tournament
+            .and_then(|t| self.tournaments.get(t))
+            .map_or(self.limits, |overrides| overrides.apply(self.limits))
+    }
+}
+
+/**
+ * The limits on the scores that can be submitted, and how precisely they are shown
+ *
+ * The defaults can be changed for the whole deployment with `MAX_METERS` and `RESOLUTION_CM`,
+ * and for single tournaments in the JSON file `TOURNAMENT_CONFIG` points to, e.g.
+ *
+ * ```json
+ * { "<tournament id>": { "max_meters": 40, "resolution_cm": 0.5 } }
+ * ```
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// The farthest a score can be from the hole, in whole meters
+    pub max_meters: u8,
+    /// The smallest step distances are measured in, in centimeters
+    pub resolution_cm: f64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_meters: 25,
+            resolution_cm: 1.0,
+        }
+    }
+}
+
+/// The settings for a single tournament in the `TOURNAMENT_CONFIG` file
+#[derive(Debug, Deserialize, Default)]
+struct Overrides {
+    max_meters: Option<u8>,
+    resolution_cm: Option<f64>,
+}
+
+impl Overrides {
+    /// `limits` with these overrides
+    fn apply(&self, limits: Limits) -> Limits {
+        Limits {
+            max_meters: self.max_meters.unwrap_or(limits.max_meters),
+            resolution_cm: self.resolution_cm.unwrap_or(limits.resolution_cm),
+        }
+    }
+}
+
+impl Limits {
+    /// Fails if the resolution makes no sense, `context` says where it was set
+    fn validate(&self, context: &str) -> Result<(), Error> {
+        if !(self.resolution_cm > 0.0 && self.resolution_cm <= 100.0) {
+            let error = format!("{} is not between 0 and 100", self.resolution_cm);
+            return Err(critical(context, error));
+        }
+        Ok(())
+    }
+
+    /// The most centimeters that can be entered next to the whole meters
+    pub fn max_cm(&self) -> f64 {
+        100.0 - self.resolution_cm
+    }
+
+    /// Whether `cm` can be entered next to the whole meters
+    pub fn is_valid_cm(&self, cm: f64) -> bool {
+        let steps = cm / self.resolution_cm;
+        (0.0..=self.max_cm()).contains(&cm) && (steps - steps.round()).abs() < 1e-6
+    }
+
+    /// How many decimals distances in meters are shown with
+    pub fn decimals(&self) -> usize {
+        let resolution = self.resolution_cm.to_string();
+        2 + resolution.split_once('.').map_or(0, |(_, d)| d.len())
+    }
+}
+
+/**
+ * How many scores a client can submit to a hole in a period of time
+ *
+ * Set with `RATE_LIMIT=<submissions>/<seconds>`, `0` turns it off. The default is 10 scores in
+ * 10 minutes, enough for a group sharing a phone.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub submissions: usize,
+    pub seconds: u64,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            submissions: 10,
+            seconds: 600,
+        }
+    }
+}
+
+impl RateLimit {
+    fn from_env() -> Result<Self, Error> {
+        let Ok(value) = env::var("RATE_LIMIT") else {
+            return Ok(Self::default());
+        };
+        let (submissions, seconds) = value.split_once('/').unwrap_or((&value, "0"));
+        let submissions = submissions
+            .trim()
+            .parse()
+            .map_err(|e| critical("RATE_LIMIT", e))?;
+        let seconds = seconds
+            .trim()
+            .parse()
+            .map_err(|e| critical("RATE_LIMIT", e))?;
+        Ok(Self {
+            submissions,
+            seconds,
+        })
+    }
+}
+
+/**
+ * Which submissions are left out because the score is already on the hole
+ *
+ * Set with `DUPLICATES`:
+ * - `key`: the same form submitted twice, e.g. by double-tapping the button, is only submitted
+ *   once. Submissions without the form's key are compared like with `content`. The default
+ * - `content`: also leaves out scores with the same distance and player as one on the hole, see
+ *   [`crate::post`]. Players with the same name and distance get merged unless they have member
+ *   numbers
+ * - `off`: submits everything
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Duplicates {
+    Off,
+    #[default]
+    Key,
+    Content,
+}
+
+impl Duplicates {
+    fn from_env() -> Result<Self, Error> {
+        match env::var("DUPLICATES").as_deref().map(str::trim) {
+            Err(_) | Ok("key") => Ok(Self::Key),
+            Ok("content") => Ok(Self::Content),
+            Ok("off") => Ok(Self::Off),
+            Ok(value) => Err(critical(
+                "DUPLICATES",
+                format!("{value} is not key, content or off"),
+            )),
+        }
+    }
+}
+
+/**
+ * Whether member numbers are shown on the pages
+ *
+ * Set with `MEMBER_NUMBERS=show` or `hide`. They're still submitted to the API server when
+ * they're hidden, so the organisers can see them there
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MemberNumbers {
+    #[default]
+    Show,
+    Hide,
+}
+
+impl MemberNumbers {
+    fn from_env() -> Result<Self, Error> {
+        match env::var("MEMBER_NUMBERS").as_deref().map(str::trim) {
+            Err(_) | Ok("show") => Ok(Self::Show),
+            Ok("hide") => Ok(Self::Hide),
+            Ok(value) => Err(critical(
+                "MEMBER_NUMBERS",
+                format!("{value} is not show or hide"),
+            )),
+        }
+    }
+}
+
+/**
+ * Whether players can change and withdraw scores on the API server after submitting them
+ *
+ * Set with `SCORE_CHANGES=on` or `off`. Off by default, because it needs a `DELETE` route the
+ * API server's OpenAPI spec doesn't have, see [`crate::edit::Edit`]. The `file://` backend always
+ * allows it
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreChanges {
+    #[default]
+    Off,
+    On,
+}
+
+impl ScoreChanges {
+    fn from_env() -> Result<Self, Error> {
+        match env::var("SCORE_CHANGES").as_deref().map(str::trim) {
+            Err(_) | Ok("off") => Ok(Self::Off),
+            Ok("on") => Ok(Self::On),
+            Ok(value) => Err(critical(
+                "SCORE_CHANGES",
+                format!("{value} is not on or off"),
+            )),
+        }
+    }
+}
+
+/**
+ * How many seconds responses from the API server are reused for
+ *
+ * Set with `CACHE_SECONDS`, `0` turns it off. The default of 5 seconds is short enough that
+ * nobody notices, and spares the API server when everyone reloads the leaderboard at once
+ */
+fn cache_seconds() -> Result<u64, Error> {
+    Ok(var("CACHE_SECONDS")?.unwrap_or(5))
+}
+
+/// The value of the environment variable `name`, if it is set
+fn var<T: std::str::FromStr>(name: &str) -> Result<Option<T>, Error>
+where
+    T::Err: std::fmt::Display,
+{
+    match env::var(name) {
+        Ok(value) => Ok(Some(value.trim().parse().map_err(|e| critical(name, e))?)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn critical(context: &str, error: impl std::fmt::Display) -> Error {
+    Error::CriticalServer(format!("{context}: {error}").into())
+}
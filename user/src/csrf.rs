@@ -0,0 +1,199 @@
+use crate::error::Error;
+use crate::get::ViewHolePage;
+use crate::request::Request;
+use crate::response::Response;
+use crate::state::{now, Store};
+use hmac_sha256::HMAC;
+use std::env;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::{Arc, OnceLock};
+
+/// How long a submission form can be open before it has to be loaded again, in seconds
+const LIFETIME: u64 = 2 * 60 * 60;
+
+/**
+ * The browser a form is shown in, identified by the random value of its `csrf` cookie
+ *
+ * The tokens are bound to it (a double-submit cookie), so a form is only accepted together with
+ * the cookie of the browser it was shown in. Other sites can neither read nor set the cookie, so
+ * a token they got hold of is useless to them
+ */
+pub struct Client {
+    pub id: String,
+    /// The browser doesn't have the cookie yet, so the response has to set it
+    new: bool,
+}
+
+impl Client {
+    /// The client from the cookie of `request`, or a new one if it has none
+    pub fn from_request(request: &Request) -> Result<Self, Error> {
+        match request.cookie("csrf").filter(|id| is_id(id)) {
+            Some(id) => Ok(Self {
+                id: id.to_owned(),
+                new: false,
+            }),
+            None => Ok(Self {
+                id: random_hex(16)?,
+                new: true,
+            }),
+        }
+    }
+
+    /// Gives the browser the cookie with `response`, if it doesn't have it already
+    pub fn set_cookie(&self, response: Response) -> Response {
+        match self.new {
+            true => response.with_header(
+                "Set-Cookie",
+                format!(
+                    "csrf={}; Path=/; Max-Age=31536000; HttpOnly; SameSite=Lax",
+                    self.id
+                ),
+            ),
+            false => response,
+        }
+    }
+}
+
+/// Whether `id` looks like one from [`Client::from_request`]
+fn is_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/**
+ * A token for the submission form for `hole`, shown to the [`Client`] with the id `client`
+ *
+ * It's the time it expires and an HMAC of that, the hole and the client, so a score can only be
+ * submitted with a form that this server has rendered for that hole and browser recently
+ */
+pub fn token(hole: &ViewHolePage, client: &str, secret: &Secret) -> Result<String, Error> {
+    let expires = now()? + LIFETIME;
+    Ok(format!(
+        "{expires}.{}",
+        sign(hole, client, expires, secret)?
+    ))
+}
+
+/**
+ * Fails with [`Error::InvalidToken`] unless `token` is from [`token`] for `hole` and not expired,
+ * and `request` has the cookie of the [`Client`] it was made for
+ */
+pub fn verify(
+    token: &str,
+    hole: &ViewHolePage,
+    request: &Request,
+    secret: &Secret,
+) -> Result<(), Error> {
+    let client = request.cookie("csrf").ok_or(Error::InvalidToken)?;
+    let (expires, mac) = token.split_once('.').ok_or(Error::InvalidToken)?;
+    let expires: u64 = expires.parse().map_err(|_| Error::InvalidToken)?;
+    if !same_mac(mac, &sign(hole, client, expires, secret)?) || expires < now()? {
+        return Err(Error::InvalidToken);
+    }
+    Ok(())
+}
+
+fn sign(hole: &ViewHolePage, client: &str, expires: u64, secret: &Secret) -> Result<String, Error> {
+    let message = format!(
+        "{}/{}/{}/{client}/{expires}",
+        hole.user, hole.tournament, hole.hole
+    );
+    mac(&message, secret)
+}
+
+/// An HMAC of `message` in hex, with the same key as the tokens
+pub fn mac(message: &str, secret: &Secret) -> Result<String, Error> {
+    Ok(hex(&HMAC::mac(message, secret.get()?)))
+}
+
+/// Compares every byte of two MACs, so the time it takes doesn't say how much of `mac` was right
+pub fn same_mac(mac: &str, expected: &str) -> bool {
+    mac.len() == expected.len()
+        && mac
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// `bytes` random bytes in hex, for secrets and ids that can't be guessed
+pub fn random_hex(bytes: usize) -> Result<String, Error> {
+    let mut random = vec![0; bytes];
+    File::open("/dev/urandom")?.read_exact(&mut random)?;
+    Ok(hex(&random))
+}
+
+/**
+ * The key the tokens are signed with
+ *
+ * Taken from `CSRF_SECRET`, otherwise it's generated the first time it's needed and kept in the
+ * [`Store`], so that it's the same for every process. Clones share the loaded key.
+ */
+#[derive(Clone)]
+pub struct Secret {
+    key: Arc<OnceLock<Vec<u8>>>,
+    store: Store,
+}
+
+impl Secret {
+    /// `CSRF_SECRET`, or the one in `store`
+    pub fn from_env(store: Store) -> Self {
+        let key = OnceLock::new();
+        if let Some(secret) = env::var("CSRF_SECRET").ok().filter(|s| !s.is_empty()) {
+            let _ = key.set(secret.into_bytes());
+        }
+        Self {
+            key: Arc::new(key),
+            store,
+        }
+    }
+
+    fn get(&self) -> Result<&[u8], Error> {
+        if let Some(key) = self.key.get() {
+            return Ok(key);
+        }
+        let key = load_or_generate_secret(&self.store)?;
+        Ok(self.key.get_or_init(|| key))
+    }
+}
+
+/// Without the key, so it doesn't end up in the logs
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Secret").finish_non_exhaustive()
+    }
+}
+
+fn load_or_generate_secret(store: &Store) -> Result<Vec<u8>, Error> {
+    let path = store.dir()?.join("csrf_secret");
+    match fs::read(&path) {
+        Ok(secret) => return Ok(secret),
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        Err(_) => {}
+    }
+
+    let secret = random_hex(32)?.into_bytes();
+
+    // Written to a temporary file first, so other processes never read half a secret.
+    // If another process got there first its secret is used instead
+    let temporary = path.with_extension(std::process::id().to_string());
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&temporary)?
+        .write_all(&secret)?;
+    let linked = fs::hard_link(&temporary, &path);
+    fs::remove_file(&temporary)?;
+    match linked {
+        Ok(()) => Ok(secret),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(fs::read(&path)?),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,64 @@
+use crate::i18n::Lang;
+
+/**
+ * A CSV file for copying scores into a spreadsheet
+ *
+ * Spreadsheets expect the fields to be separated by ';' in locales that use ',' as the decimal
+ * separator (like danish), so the separator follows the language.
+ * The file starts with a byte order mark, otherwise Excel doesn't read it as UTF-8.
+ */
+pub struct Csv {
+    /// Suggested to the browser when the file is downloaded, without the extension
+    pub filename: String,
+    separator: char,
+    body: String,
+}
+
+impl Csv {
+    pub fn new(filename: String, lang: Lang) -> Self {
+        let separator = match lang.decimal_separator() {
+            ',' => ';',
+            _ => ',',
+        };
+        Self {
+            filename,
+            separator,
+            body: "\u{feff}".to_owned(),
+        }
+    }
+
+    /// Appends a row with `fields`
+    pub fn row<S: AsRef<str>>(&mut self, fields: impl IntoIterator<Item = S>) {
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                self.body.push(self.separator);
+            }
+            self.push_field(field.as_ref());
+        }
+        self.body.push_str("\r\n");
+    }
+
+    /**
+     * Quotes `field` if needed
+     *
+     * Names come from whoever submitted the score, so fields that a spreadsheet would run as a
+     * formula get a leading `'`
+     */
+    fn push_field(&mut self, field: &str) {
+        let formula = field.starts_with(['=', '+', '-', '@', '\t', '\r']);
+        let quote = formula || field.contains([self.separator, '"', '\n', '\r']);
+        if !quote {
+            return self.body.push_str(field);
+        }
+        self.body.push('"');
+        if formula {
+            self.body.push('\'');
+        }
+        self.body.push_str(&field.replace('"', "\"\""));
+        self.body.push('"');
+    }
+
+    pub fn into_body(self) -> String {
+        self.body
+    }
+}
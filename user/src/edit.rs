@@ -0,0 +1,93 @@
+use crate::config::Config;
+use crate::csrf;
+use crate::error::Error;
+use crate::get::{Score, ViewHolePage};
+use crate::request::Request;
+use crate::state::now;
+use serde::{Deserialize, Serialize};
+
+/// How long a player can change a score after submitting it, in seconds
+const GRACE_PERIOD: u64 = 10 * 60;
+
+/**
+ * A score that was just submitted, which the player who submitted it can still change
+ *
+ * It's kept in the state store under a random id, which the player gets in a signed cookie.
+ * Changes are forwarded to the API server by [`crate::post`], so they're only offered when it
+ * can delete scores, see [`crate::backend::Backend::can_delete`].
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Edit {
+    #[serde(skip)]
+    pub id: String,
+    pub user: String,
+    pub tournament: String,
+    pub hole: u8,
+    /// The score as it is on the API server
+    pub score: Score,
+    /// Seconds since the unix epoch
+    pub expires: u64,
+}
+
+impl Edit {
+    /// Lets the player who just submitted `score` to `hole` change it for the [`GRACE_PERIOD`]
+    pub fn grant(hole: &ViewHolePage, score: Score, config: &Config) -> Result<Self, Error> {
+        let edit = Self {
+            id: csrf::random_hex(16)?,
+            user: hole.user.clone(),
+            tournament: hole.tournament.clone(),
+            hole: hole.hole,
+            score,
+            expires: now()? + GRACE_PERIOD,
+        };
+        edit.save(config)?;
+        Ok(edit)
+    }
+
+    /// The score the `edit` cookie in `request` allows the player to change, if any
+    pub fn from_request(request: &Request, config: &Config) -> Result<Option<Self>, Error> {
+        let Some((id, mac)) = request.cookie("edit").and_then(|c| c.split_once('.')) else {
+            return Ok(None);
+        };
+        if !csrf::same_mac(mac, &csrf::mac(&format!("edit/{id}"), &config.secret)?) {
+            return Ok(None);
+        }
+        let Some(edit) = config.state.get("edits", id)? else {
+            return Ok(None);
+        };
+        let edit = Self {
+            id: id.to_owned(),
+            ..serde_json::from_str(&edit)?
+        };
+        Ok((edit.expires > now()?).then_some(edit))
+    }
+
+    pub fn is_for(&self, hole: &ViewHolePage) -> bool {
+        self.user == hole.user && self.tournament == hole.tournament && self.hole == hole.hole
+    }
+
+    /// Remembers that the score is now `self.score`
+    pub fn save(&self, config: &Config) -> Result<(), Error> {
+        let value = serde_json::to_string(self)?;
+        let ttl = self.expires.saturating_sub(now()?);
+        config
+            .state
+            .update("edits", &self.id, ttl, |v| *v = Some(value))
+    }
+
+    /// Forgets the score after it has been withdrawn, returns the `Set-Cookie` header for that
+    pub fn revoke(self, config: &Config) -> Result<&'static str, Error> {
+        config.state.update("edits", &self.id, 0, |v| *v = None)?;
+        Ok("edit=; Path=/; Max-Age=0")
+    }
+
+    /// The `Set-Cookie` header that gives the player the signed id
+    pub fn cookie(&self, config: &Config) -> Result<String, Error> {
+        let mac = csrf::mac(&format!("edit/{}", self.id), &config.secret)?;
+        let max_age = self.expires.saturating_sub(now()?);
+        Ok(format!(
+            "edit={}.{mac}; Path=/; Max-Age={max_age}; HttpOnly; SameSite=Lax",
+            self.id
+        ))
+    }
+}
@@ -1,15 +1,31 @@
+use crate::get::Format;
+use crate::i18n::Lang;
+use crate::response::{ContentType, Response, Status};
 use std::fmt;
 
+const ISSUE_TRACKER: &str =
+    "https://github.com/itzgoldenleonard/bullseyegolf-frontend-light/issues";
+
 #[derive(Debug)]
 pub enum Error {
     /// Query string does not exist or is not valid
     InvalidQueryString,
     /// Generic network error, this will probably never actually happen
     Network(reqwest::Error),
-    /// Used in [`crate::post`] if no valid query string can be found in the Referer HTTP header
-    Referer,
     /// Usid in [`crate::post`] if the HTML form submitted is not valid
     InvalidForm(serde_urlencoded::de::Error),
+    /// Used when scores are submitted to a tournament that isn't active anymore
+    TournamentClosed,
+    /// Used in [`crate::post`] if the form has no valid token, see [`crate::csrf`]
+    InvalidToken,
+    /// Used in [`crate::post`] when a client submits too many scores, with the seconds until it
+    /// can submit again
+    TooManyRequests(u64),
+    /// Used when a score is changed after its [`crate::edit::Edit`] has expired, or from another
+    /// browser
+    EditExpired,
+    /// The user, tournament or hole doesn't exist in [`crate::backend::JsonFile`]
+    NotFound,
     /// Anything that warrants a bug report
     CriticalServer(Box<dyn std::error::Error>),
     BackendConnection(reqwest::Error),
@@ -29,12 +45,24 @@ impl From<std::env::VarError> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::CriticalServer(Box::new(error))
+    }
+}
+
 impl From<std::time::SystemTimeError> for Error {
     fn from(error: std::time::SystemTimeError) -> Self {
         Self::CriticalServer(Box::new(error))
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::CriticalServer(Box::new(error))
+    }
+}
+
 impl From<reqwest::Error> for Error {
     /// All possible types of network errors
     /// - API server sends an error
@@ -55,42 +83,127 @@ impl From<reqwest::Error> for Error {
     }
 }
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        // TODO: Make link labels make sense out of context
+impl Error {
+    /// The HTTP status to respond with
+    pub fn status(&self) -> Status {
         use Error::*;
-        let headers =
-            "\r\nContent-Type: text/html; charset=utf-8\r\nContent-Language: da\r\n\r\n\r\n"; // There could be a heading here
-        let issue_tracker = "<a href=\"https://github.com/itzgoldenleonard/bullseyegolf-frontend-light/issues\">https://github.com/itzgoldenleonard/bullseyegolf-frontend-light/issues</a>";
+        match self {
+            CriticalServer(_) | Network(_) => Status::InternalServerError,
+            InvalidQueryString | InvalidForm(_) => Status::BadRequest,
+            BackendConnection(_) => Status::ServiceUnavailable,
+            BackendStatus(_) => Status::BadGateway,
+            TournamentClosed | InvalidToken | EditExpired => Status::Forbidden,
+            TooManyRequests(_) => Status::TooManyRequests,
+            NotFound => Status::NotFound,
+        }
+    }
 
+    /**
+     * The error page in `lang` and `format`
+     *
+     * JSON clients get an object with the status code and the message as plain text. CSV has no
+     * sensible way to show an error, so those get the plain text message on its own.
+     */
+    pub fn into_response(self, lang: Lang, format: Format) -> Response {
+        let status = self.status();
+        let mut response = Response::builder(status)
+            .content_language(lang.code())
+            .cache_control("no-store");
         match self {
-            CriticalServer(e) => {
-                write!(f, "Status: 500{headers} Der skete en fejl på serveren, dette er en bug. Rapporter fejlen her: {issue_tracker} <br> Inkluder følgende informationer i fejlrapporten: Sidens URL, denne fejlbesked: <pre>{e}</pre>")
-            }
-            InvalidQueryString => {
-                write!(f, "Status: 400{headers} Der er en fejl i URL'en. Tjek at du har stavet den rigtigt og at du har fået det rigtige link. <br> URL'en burde ende med: <pre>....org/u?u=<u>brugernavn</u></pre>")
-            }
-            Network(e) => {
-                write!(f, "Status: 500{headers} Der skete en ukendt netværksfejl, dette er muligvis en bug. Fejlbesked: <pre>{e}</pre>")
+            Error::BackendConnection(_) => response = response.header("Retry-After", "60"),
+            Error::TooManyRequests(seconds) => {
+                response = response.header("Retry-After", seconds.to_string())
             }
-            BackendConnection(e) => {
-                write!(f, "Status: 503\r\nRetry-After: 60{headers} Bullseyegolf light kunne ikke kommunikere med API serveren, prøv igen senere.<br>Fejlbesked:<pre>{e}</pre>")
-            }
-            BackendStatus(e) => {
-                write!(f, "Status: 502{headers} Der skete en fejl på API serveren. <pre>{e}</pre>")
-            }
-            Referer => {
-                write!(
-                    f,
-                    "Status: 400{headers} Bullseyegolf light kunne ikke afgøre hvilket hul din notering skal indsendes til. <br><br> Dette kan ske hvis du har indsendt din notering uden at vælge et hul først. Hvis det er tilfældet, så gå til hullet du ønsker at indsende noteringen til og vælg 'Indsend notering' <br><br>Dette kan også ske hvis din browsers referrer policy er for streng. Bullseyegolf light bruger Referer til at afgøre hvor din notering skal sendes hen og virker derfor ikke hvis din referer policy er indstillet til 'no-referrer'. Indstillingen vil kun være sat til 'no-referrer' hvis du selv har gjort det. Du kan prøve med en anden browser, eller nulstille indstillingen.<br><a href=\"https://www.technipages.com/firefox-enable-disable-referrer/\">Hvis du bruger firefox kan du læse hvordan du gør her</a><br><a href=\"https://developer.chrome.com/blog/referrer-policy-new-chrome-default/#test-the-change-and-figure-out-if-this-will-impact-your-site\">Hvis du bruger chromium (chrome, edge, Samsung internet, brave, vivaldi, opera osv.) kan du læse hvordan du gør her</a>."
-                )
-            }
-            InvalidForm(e) => {
-                write!(
-                    f,
-                    "Status: 400{headers} Dataen du har indsendt er ikke i det rigtige format, dette burde ikke ske. Luk siden og prøv igen.<br/> Fejlbesked:<pre>{e}</pre>"
-                )
+            _ => {}
+        }
+        match format {
+            Format::Html => response.body(self.message(lang)),
+            Format::Json => {
+                let body = serde_json::json!({
+                    "status": status.code(),
+                    "message": self.plain_message(lang),
+                });
+                response
+                    .content_type(ContentType::Json)
+                    .body(body.to_string())
             }
+            Format::Csv => response
+                .content_type(ContentType::Plain)
+                .body(self.plain_message(lang)),
         }
     }
+
+    /// The explanation shown to the user
+    pub fn message(&self, lang: Lang) -> String {
+        let (template, error) = self.template(lang);
+        template
+            .replace(
+                "{issue_tracker}",
+                &format!("<a href=\"{ISSUE_TRACKER}\">{ISSUE_TRACKER}</a>"),
+            )
+            .replace("{error}", &error)
+    }
+
+    /// [`Error::message`] without the HTML
+    pub fn plain_message(&self, lang: Lang) -> String {
+        let (template, error) = self.template(lang);
+        strip_tags(template)
+            .replace("{issue_tracker}", ISSUE_TRACKER)
+            .replace("{error}", &error)
+    }
+
+    /// The message in `lang` with placeholders, and the error to fill in for `{error}`
+    fn template(&self, lang: Lang) -> (&'static str, String) {
+        use Error::*;
+        let text = lang.text();
+        match self {
+            CriticalServer(e) => (text.error_critical_server, e.to_string()),
+            InvalidQueryString => (text.error_invalid_query_string, String::new()),
+            Network(e) => (text.error_network, e.to_string()),
+            BackendConnection(e) => (text.error_backend_connection, e.to_string()),
+            BackendStatus(e) => (text.error_backend_status, e.to_string()),
+            InvalidForm(e) => (text.error_invalid_form, e.to_string()),
+            TournamentClosed => (text.error_tournament_closed, String::new()),
+            InvalidToken => (text.error_invalid_token, String::new()),
+            TooManyRequests(_) => (text.error_too_many_requests, String::new()),
+            EditExpired => (text.error_edit_expired, String::new()),
+            NotFound => (text.error_not_found, String::new()),
+        }
+    }
+}
+
+/// Turns the markup in an error template into plain text, with line breaks for `<br>` and `<pre>`
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some((before, after)) = rest.split_once('<') {
+        let Some((tag, after)) = after.split_once('>') else {
+            break;
+        };
+        text.push_str(before);
+        if tag.starts_with("br") || tag.trim_start_matches('/') == "pre" {
+            text.push('\n');
+        }
+        rest = after;
+    }
+    text.push_str(rest);
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl From<Error> for Response {
+    /// Used when the language isn't known yet
+    fn from(error: Error) -> Self {
+        error.into_response(Lang::default(), Format::default())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        // TODO: Make link labels make sense out of context
+        write!(f, "{}", self.message(Lang::default()))
+    }
 }
@@ -0,0 +1,548 @@
+use crate::config::Config;
+use crate::error::Error;
+use crate::request::{Request, MAX_BODY};
+use crate::response::{Response, Status};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem::ManuallyDrop;
+use std::net::TcpListener;
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::thread;
+
+const VERSION: u8 = 1;
+
+const BEGIN_REQUEST: u8 = 1;
+const ABORT_REQUEST: u8 = 2;
+const END_REQUEST: u8 = 3;
+const PARAMS: u8 = 4;
+const STDIN: u8 = 5;
+const STDOUT: u8 = 6;
+const GET_VALUES: u8 = 9;
+const GET_VALUES_RESULT: u8 = 10;
+const UNKNOWN_TYPE: u8 = 11;
+
+const RESPONDER: u16 = 1;
+const KEEP_CONN: u8 = 1;
+
+const REQUEST_COMPLETE: u8 = 0;
+const CANT_MPX_CONN: u8 = 1;
+const UNKNOWN_ROLE: u8 = 3;
+
+/// The most bytes of params a request can have, far more than the headers browsers send
+const MAX_PARAMS: usize = 64 * 1024;
+
+/**
+ * Whether the web server has handed us a listening socket on stdin, like FastCGI servers do
+ *
+ * A connected socket on stdin doesn't count, some web servers use those to pass the request body
+ * to CGI scripts. Only listening sockets fail `getpeername` with `ENOTCONN`.
+ */
+pub fn stdin_is_listener() -> bool {
+    let is_socket = fs::metadata("/dev/stdin")
+        .map(|m| m.file_type().is_socket())
+        .unwrap_or(false);
+    // SAFETY: The stream is never dropped, so stdin is not closed
+    let stdin = ManuallyDrop::new(unsafe { UnixStream::from_raw_fd(io::stdin().as_raw_fd()) });
+    is_socket
+        && stdin
+            .peer_addr()
+            .is_err_and(|e| e.kind() == io::ErrorKind::NotConnected)
+}
+
+/**
+ * Serves FastCGI requests on the listening socket passed as stdin
+ *
+ * `server` is the URL of the API server and `config` the settings read at startup.
+ * This is how lighttpd (and most other web servers) start FastCGI applications themselves
+ */
+pub fn serve_stdin(server: &'static str, config: &'static Config) -> io::Result<()> {
+    // SAFETY: The web server hands us ownership of a listening socket as file descriptor 0
+    let unix = unsafe { UnixListener::from_raw_fd(io::stdin().as_raw_fd()) };
+    if unix.local_addr().is_ok() {
+        serve(unix.incoming(), server, config)
+    } else {
+        // SAFETY: Same socket as above, it is just not a unix socket
+        let tcp = unsafe { TcpListener::from_raw_fd(unix.into_raw_fd()) };
+        serve(tcp.incoming(), server, config)
+    }
+}
+
+/**
+ * Serves FastCGI requests on `addr`
+ *
+ * `addr` is treated as the path to a unix socket if it contains a '/', otherwise as a TCP address
+ */
+pub fn listen(addr: &str, server: &'static str, config: &'static Config) -> io::Result<()> {
+    if addr.contains('/') {
+        let _ = fs::remove_file(addr);
+        serve(UnixListener::bind(addr)?.incoming(), server, config)
+    } else {
+        serve(TcpListener::bind(addr)?.incoming(), server, config)
+    }
+}
+
+/// Handles every connection on its own thread, the process stays resident between requests
+fn serve<S>(
+    incoming: impl Iterator<Item = io::Result<S>>,
+    server: &'static str,
+    config: &'static Config,
+) -> io::Result<()>
+where
+    S: Read + Write + Send + TryClone + 'static,
+{
+    for stream in incoming {
+        let stream = match stream {
+            Ok(s) => s,
+            // The socket isn't listening, trying again won't help
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => return Err(e),
+            Err(e) => {
+                eprintln!("Failed to accept FastCGI connection: {e}");
+                continue;
+            }
+        };
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, server, config) {
+                eprintln!("Failed to handle FastCGI connection: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+/// The streams that [`serve`] accepts need to be both read from and written to at once
+pub trait TryClone: Sized {
+    fn try_clone(&self) -> io::Result<Self>;
+}
+
+impl TryClone for std::net::TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        self.try_clone()
+    }
+}
+
+impl TryClone for UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        self.try_clone()
+    }
+}
+
+/// A single record of the FastCGI protocol
+struct Record {
+    kind: u8,
+    request_id: u16,
+    content: Vec<u8>,
+}
+
+impl Record {
+    /// Reads a record, returns [`None`] if the web server has closed the connection
+    fn read(r: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut header = [0; 8];
+        match r.read_exact(&mut header) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            r => r?,
+        }
+        if header[0] != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unsupported FastCGI version",
+            ));
+        }
+        let request_id = u16::from_be_bytes([header[2], header[3]]);
+        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        let padding = header[6] as usize;
+
+        let mut content = vec![0; length + padding];
+        r.read_exact(&mut content)?;
+        content.truncate(length);
+
+        Ok(Some(Self {
+            kind: header[1],
+            request_id,
+            content,
+        }))
+    }
+
+    fn write(w: &mut impl Write, kind: u8, request_id: u16, content: &[u8]) -> io::Result<()> {
+        let [id_high, id_low] = request_id.to_be_bytes();
+        let [len_high, len_low] = (content.len() as u16).to_be_bytes();
+        w.write_all(&[VERSION, kind, id_high, id_low, len_high, len_low, 0, 0])?;
+        w.write_all(content)
+    }
+}
+
+/// The request currently being received on a connection
+struct PendingRequest {
+    id: u16,
+    keep_conn: bool,
+    params: Vec<u8>,
+    params_done: bool,
+    stdin: Vec<u8>,
+    /**
+     * Set when the params or stdin would be longer than [`MAX_PARAMS`] or [`MAX_BODY`], it's
+     * responded with instead. What doesn't fit is dropped, so the web server can't make the
+     * process use more and more memory
+     */
+    rejected: Option<Status>,
+}
+
+/**
+ * Handles all the requests sent on a single connection
+ *
+ * Requests are not multiplexed, the web server is told so with `FCGI_MPXS_CONNS` if it asks
+ */
+fn handle<S: Read + Write + TryClone>(stream: S, server: &str, config: &Config) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    let mut request: Option<PendingRequest> = None;
+
+    while let Some(record) = Record::read(&mut reader)? {
+        match record.kind {
+            BEGIN_REQUEST => {
+                if record.content.len() < 3 {
+                    let error = "FCGI_BEGIN_REQUEST without a role and flags";
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+                }
+                let role = u16::from_be_bytes([record.content[0], record.content[1]]);
+                let keep_conn = record.content[2] & KEEP_CONN != 0;
+                if role != RESPONDER {
+                    end_request(&mut writer, record.request_id, UNKNOWN_ROLE)?;
+                } else if request.is_some() {
+                    end_request(&mut writer, record.request_id, CANT_MPX_CONN)?;
+                } else {
+                    request = Some(PendingRequest {
+                        id: record.request_id,
+                        keep_conn,
+                        params: Vec::new(),
+                        params_done: false,
+                        stdin: Vec::new(),
+                        rejected: None,
+                    });
+                }
+            }
+            ABORT_REQUEST if request.as_ref().is_some_and(|r| r.id == record.request_id) => {
+                request = None;
+                end_request(&mut writer, record.request_id, REQUEST_COMPLETE)?;
+            }
+            PARAMS => {
+                if let Some(r) = request.as_mut().filter(|r| r.id == record.request_id) {
+                    r.params_done = record.content.is_empty();
+                    if r.params.len() + record.content.len() > MAX_PARAMS {
+                        r.rejected.get_or_insert(Status::BadRequest);
+                    } else {
+                        r.params.extend(record.content);
+                    }
+                }
+            }
+            STDIN => {
+                let Some(r) = request.as_mut().filter(|r| r.id == record.request_id) else {
+                    continue;
+                };
+                if !record.content.is_empty() {
+                    if (r.stdin.len() + record.content.len()) as u64 > MAX_BODY {
+                        r.rejected.get_or_insert(Status::ContentTooLarge);
+                    } else {
+                        r.stdin.extend(record.content);
+                    }
+                    continue;
+                }
+                if !r.params_done {
+                    continue;
+                }
+                let Some(r) = request.take() else { continue };
+
+                let request = Request::from_cgi(decode_pairs(&r.params), &r.stdin[..]);
+                let response = match (r.rejected, request) {
+                    (Some(status), _) => Response::plain(status),
+                    (None, Ok(request)) => crate::respond(&request, server, config),
+                    (None, Err(e)) => Error::CriticalServer(Box::new(e)).into(),
+                };
+                let mut output = Vec::new();
+                response.write_cgi(&mut output)?;
+                for chunk in output.chunks(u16::MAX as usize) {
+                    Record::write(&mut writer, STDOUT, r.id, chunk)?;
+                }
+                Record::write(&mut writer, STDOUT, r.id, &[])?;
+                end_request(&mut writer, r.id, REQUEST_COMPLETE)?;
+                if !r.keep_conn {
+                    return Ok(());
+                }
+            }
+            GET_VALUES => {
+                let mut result = Vec::new();
+                for name in decode_pairs(&record.content).keys() {
+                    let value = match name.as_str() {
+                        "FCGI_MAX_CONNS" | "FCGI_MAX_REQS" => "1000",
+                        "FCGI_MPXS_CONNS" => "0",
+                        _ => continue,
+                    };
+                    encode_pair(&mut result, name, value);
+                }
+                Record::write(&mut writer, GET_VALUES_RESULT, 0, &result)?;
+                writer.flush()?;
+            }
+            // Records of unknown types for a request are ignored like the ones of other requests
+            kind if record.request_id == 0 => {
+                let mut body = [0; 8];
+                body[0] = kind;
+                Record::write(&mut writer, UNKNOWN_TYPE, 0, &body)?;
+                writer.flush()?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn end_request(w: &mut impl Write, request_id: u16, protocol_status: u8) -> io::Result<()> {
+    Record::write(
+        w,
+        END_REQUEST,
+        request_id,
+        &[0, 0, 0, 0, protocol_status, 0, 0, 0],
+    )?;
+    w.flush()
+}
+
+/// Decodes the name-value pairs used by `FCGI_PARAMS` and `FCGI_GET_VALUES`
+fn decode_pairs(mut data: &[u8]) -> HashMap<String, String> {
+    fn length(data: &mut &[u8]) -> Option<usize> {
+        let first = *data.first()?;
+        if first & 0x80 == 0 {
+            *data = &data[1..];
+            Some(first as usize)
+        } else {
+            let bytes: [u8; 4] = data.get(..4)?.try_into().ok()?;
+            *data = &data[4..];
+            Some((u32::from_be_bytes(bytes) & 0x7fff_ffff) as usize)
+        }
+    }
+
+    let mut pairs = HashMap::new();
+    while let (Some(name_len), Some(value_len)) = (length(&mut data), length(&mut data)) {
+        let (Some(name), Some(value)) = (
+            data.get(..name_len),
+            data.get(name_len..name_len + value_len),
+        ) else {
+            break;
+        };
+        pairs.insert(
+            String::from_utf8_lossy(name).into_owned(),
+            String::from_utf8_lossy(value).into_owned(),
+        );
+        data = &data[name_len + value_len..];
+    }
+    pairs
+}
+
+fn encode_pair(out: &mut Vec<u8>, name: &str, value: &str) {
+    for len in [name.len(), value.len()] {
+        if len < 0x80 {
+            out.push(len as u8);
+        } else {
+            out.extend((len as u32 | 0x8000_0000).to_be_bytes());
+        }
+    }
+    out.extend(name.as_bytes());
+    out.extend(value.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// A record as the web server would send it
+    fn record(kind: u8, request_id: u16, content: &[u8]) -> Vec<u8> {
+        let mut record = Vec::new();
+        Record::write(&mut record, kind, request_id, content).unwrap();
+        record
+    }
+
+    fn begin(request_id: u16, flags: u8) -> Vec<u8> {
+        let [role_high, role_low] = RESPONDER.to_be_bytes();
+        record(
+            BEGIN_REQUEST,
+            request_id,
+            &[role_high, role_low, flags, 0, 0, 0, 0, 0],
+        )
+    }
+
+    /// The params of a request to `/u` with `query`, split over two records like web servers do
+    fn params(request_id: u16, method: &str, query: &str, content_length: usize) -> Vec<u8> {
+        let mut pairs = Vec::new();
+        encode_pair(&mut pairs, "REQUEST_METHOD", method);
+        encode_pair(&mut pairs, "SCRIPT_NAME", "/u");
+        encode_pair(&mut pairs, "QUERY_STRING", query);
+        encode_pair(&mut pairs, "CONTENT_LENGTH", &content_length.to_string());
+        let (first, second) = pairs.split_at(pairs.len() / 2);
+        [
+            record(PARAMS, request_id, first),
+            record(PARAMS, request_id, second),
+            record(PARAMS, request_id, &[]),
+        ]
+        .concat()
+    }
+
+    /// A whole GET request for `query`
+    fn get(request_id: u16, flags: u8, query: &str) -> Vec<u8> {
+        [
+            begin(request_id, flags),
+            params(request_id, "GET", query, 0),
+            record(STDIN, request_id, &[]),
+        ]
+        .concat()
+    }
+
+    /**
+     * Sends `input` on a connection handled by [`handle`], and returns the records it responds
+     * with until it closes the connection, or until `until` has been responded with
+     */
+    fn exchange(test: &str, input: &[u8], until: Option<u8>) -> Vec<Record> {
+        let (server, config) = crate::tests::setup(test);
+        let (mut client, stream) = UnixStream::pair().unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        thread::scope(|s| {
+            s.spawn(|| handle(stream, &server, &config));
+            client.write_all(input).unwrap();
+            let mut records = Vec::new();
+            while let Some(record) = Record::read(&mut client).unwrap() {
+                let done = Some(record.kind) == until;
+                records.push(record);
+                if done {
+                    break;
+                }
+            }
+            client.shutdown(std::net::Shutdown::Both).unwrap();
+            records
+        })
+    }
+
+    /// The `STDOUT` of `request_id` in `records`, as text
+    fn stdout(records: &[Record], request_id: u16) -> String {
+        let stdout: Vec<u8> = records
+            .iter()
+            .filter(|r| r.kind == STDOUT && r.request_id == request_id)
+            .flat_map(|r| r.content.clone())
+            .collect();
+        String::from_utf8(stdout).unwrap()
+    }
+
+    #[test]
+    fn responds_to_a_request_and_closes_the_connection() {
+        let records = exchange("fastcgi_responds", &get(1, 0, "u=demo&t=sommer&h=3"), None);
+        assert!(stdout(&records, 1).starts_with("Status: 200"));
+        let end = records.last().unwrap();
+        assert_eq!((end.kind, end.request_id), (END_REQUEST, 1));
+        assert_eq!(end.content[4], REQUEST_COMPLETE);
+    }
+
+    #[test]
+    fn keeps_the_connection_open_if_asked_to() {
+        let input = [
+            get(1, KEEP_CONN, "u=demo&t=sommer&h=3"),
+            get(2, 0, "u=demo&t=winter"),
+        ]
+        .concat();
+        let records = exchange("fastcgi_keep_conn", &input, None);
+        assert!(stdout(&records, 1).starts_with("Status: 200"));
+        assert!(stdout(&records, 2).starts_with("Status: 404"));
+        let ends = records.iter().filter(|r| r.kind == END_REQUEST).count();
+        assert_eq!(ends, 2);
+    }
+
+    #[test]
+    fn reads_the_body_from_stdin() {
+        let body = b"name=Dora&score_m=3&score_cm=5";
+        let input = [
+            begin(1, 0),
+            params(1, "POST", "u=demo&t=sommer&h=3", body.len()),
+            record(STDIN, 1, &body[..10]),
+            record(STDIN, 1, &body[10..]),
+            record(STDIN, 1, &[]),
+        ]
+        .concat();
+        // Without a token, which shows the body has been read
+        let records = exchange("fastcgi_stdin", &input, None);
+        assert!(stdout(&records, 1).starts_with("Status: 403"));
+    }
+
+    #[test]
+    fn rejects_requests_that_are_too_large() {
+        let chunk = [b'a'; 8 * 1024];
+        let mut input = begin(1, KEEP_CONN);
+        input.extend(params(1, "POST", "u=demo&t=sommer&h=3", 100_000));
+        for _ in 0..3 {
+            input.extend(record(STDIN, 1, &chunk));
+        }
+        input.extend(record(STDIN, 1, &[]));
+        input.extend(begin(2, 0));
+        for _ in 0..10 {
+            input.extend(record(PARAMS, 2, &chunk));
+        }
+        input.extend(record(PARAMS, 2, &[]));
+        input.extend(record(STDIN, 2, &[]));
+
+        let records = exchange("fastcgi_too_large", &input, None);
+        assert!(stdout(&records, 1).starts_with("Status: 413"));
+        assert!(stdout(&records, 2).starts_with("Status: 400"));
+    }
+
+    #[test]
+    fn aborted_requests_are_not_responded_to() {
+        let input = [
+            begin(1, KEEP_CONN),
+            params(1, "GET", "u=demo", 0),
+            record(ABORT_REQUEST, 1, &[]),
+            get(2, 0, "u=demo"),
+        ]
+        .concat();
+        let records = exchange("fastcgi_abort", &input, None);
+        let end = &records[0];
+        assert_eq!((end.kind, end.request_id), (END_REQUEST, 1));
+        assert_eq!(end.content[4], REQUEST_COMPLETE);
+        assert!(stdout(&records, 1).is_empty());
+        assert!(stdout(&records, 2).starts_with("Status: 200"));
+    }
+
+    #[test]
+    fn tells_the_web_server_that_requests_are_not_multiplexed() {
+        let mut names = Vec::new();
+        encode_pair(&mut names, "FCGI_MPXS_CONNS", "");
+        encode_pair(&mut names, "FCGI_UNKNOWN", "");
+        let input = record(GET_VALUES, 0, &names);
+        let records = exchange("fastcgi_get_values", &input, Some(GET_VALUES_RESULT));
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].kind, GET_VALUES_RESULT);
+        let values = decode_pairs(&records[0].content);
+        assert_eq!(
+            values,
+            HashMap::from([("FCGI_MPXS_CONNS".into(), "0".into())])
+        );
+    }
+
+    #[test]
+    fn only_unknown_management_records_are_reported() {
+        let input = [
+            begin(1, KEEP_CONN),
+            record(43, 1, b"for the request"),
+            record(42, 0, b"management"),
+        ]
+        .concat();
+        let records = exchange("fastcgi_unknown_type", &input, Some(UNKNOWN_TYPE));
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].request_id, 0);
+        assert_eq!(records[0].content, [42, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn closes_the_connection_after_a_short_begin_request() {
+        let input = [record(BEGIN_REQUEST, 1, &[0, 1]), get(2, 0, "u=demo")].concat();
+        let records = exchange("fastcgi_short_begin", &input, None);
+        assert!(records.is_empty());
+    }
+}
@@ -0,0 +1,76 @@
+use crate::i18n::Lang;
+use crate::request::Request;
+use serde_urlencoded as qs;
+
+/**
+ * The units distances can be shown in
+ *
+ * Chosen with `units=m|ft|yd` in the query string, which is remembered in a cookie of the same
+ * name. Scores are always stored in meters.
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    #[default]
+    Meters,
+    /// Feet and inches
+    Feet,
+    Yards,
+}
+
+impl Unit {
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Meters => "m",
+            Self::Feet => "ft",
+            Self::Yards => "yd",
+        }
+    }
+
+    fn from_code(code: &str) -> Option<Self> {
+        [Self::Meters, Self::Feet, Self::Yards]
+            .into_iter()
+            .find(|u| u.code().eq_ignore_ascii_case(code.trim()))
+    }
+
+    /// The units explicitly chosen with `units=` in the query string
+    pub fn from_query(query: &str) -> Option<Self> {
+        qs::from_str::<Vec<(String, String)>>(query)
+            .ok()?
+            .into_iter()
+            .find(|(k, _)| k == "units")
+            .and_then(|(_, v)| Self::from_code(&v))
+    }
+
+    /// Picks the units for `request`, see [`Unit`]
+    pub fn negotiate(request: &Request) -> Self {
+        Self::from_query(&request.query)
+            .or_else(|| request.cookie("units").and_then(Self::from_code))
+            .unwrap_or_default()
+    }
+}
+
+/**
+ * Formats a distance of `meters` for people speaking `lang` who think in `unit`
+ *
+ * Feet and inches are rounded to the nearest inch, the others are shown with `decimals`
+ * decimals
+ */
+pub fn distance(meters: f64, decimals: usize, lang: Lang, unit: Unit) -> String {
+    match unit {
+        Unit::Meters => format!("{}m", decimal(meters, decimals, lang)),
+        Unit::Yards => format!("{} yd", decimal(meters / 0.9144, decimals, lang)),
+        Unit::Feet => {
+            let inches = (meters / 0.0254).round() as u64;
+            format!("{} ft {} in", inches / 12, inches % 12)
+        }
+    }
+}
+
+/// Formats `number` with `decimals` decimals and the decimal separator used in `lang`
+pub fn decimal(number: f64, decimals: usize, lang: Lang) -> String {
+    let formatted = format!("{number:.decimals$}");
+    match lang.decimal_separator() {
+        '.' => formatted,
+        separator => formatted.replacen('.', &separator.to_string(), 1),
+    }
+}
@@ -1,28 +1,135 @@
+use crate::backend::{self, Backend};
+use crate::config::{Config, Limits, MemberNumbers};
+use crate::csrf::{self, Secret};
+use crate::csv::Csv;
+use crate::edit::Edit;
 use crate::error::Error;
-use html::inline_text::Anchor;
+use crate::format::{self, Unit};
+use crate::i18n::Lang;
+use crate::not_leading;
+use crate::post::{is_key, is_member_number, normalize, CustomScore, Field};
+use crate::request::Request;
+use crate::response::{ContentType, Response, Status};
+use crate::state::Store;
+use html::forms::Input;
+use html::inline_text::{Anchor, Span};
 use html::root::{Body, Html};
-use html::tables::{TableBody, TableHead, TableHeader, TableRow};
+use html::tables::{Table, TableBody, TableCell, TableHead, TableHeader, TableRow};
 use html::text_content::{ListItem, Paragraph, UnorderedList};
-use reqwest::blocking as http;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use serde_urlencoded as qs;
-use std::env;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tuple::Map;
 
 /// Main entrypoint for the user interface (not the submit endpoint)
-pub fn get() -> Result<String, Error> {
-    let params: Params = Params::new()?;
-    let content = params.try_into()?;
-    Ok(insert_into_template(content).to_string())
+pub fn get(
+    request: &Request,
+    server: &str,
+    config: &Config,
+    lang: Lang,
+) -> Result<Response, Error> {
+    Params::new(request, server, config, lang)?.respond(request)
 }
 
 /// All the state needed to know how to run the program
 struct Params {
-    server: String,
+    ctx: Context,
     query_args: QueryParams,
 }
 
+/// Everything needed to render a page, apart from which page it is
+pub struct Context {
+    pub backend: Box<dyn Backend>,
+    pub lang: Lang,
+    pub unit: Unit,
+    pub format: Format,
+    /// For the tournament the page is about
+    pub limits: Limits,
+    /// The score the player has just submitted and can still change
+    pub edit: Option<Edit>,
+    pub member_numbers: MemberNumbers,
+    pub state: Store,
+    pub secret: Secret,
+}
+
+impl Context {
+    /// Removes the member numbers from the names in `scores` if they shouldn't be shown
+    fn hide_member_numbers(&self, scores: &mut [Score]) {
+        if self.member_numbers == MemberNumbers::Hide {
+            for score in scores {
+                score.player_name = score.bare_name().to_owned();
+            }
+        }
+    }
+}
+
+/**
+ * What the page is sent as
+ *
+ * Chosen with `format=` in the query string or with the `Accept` header. The JSON has
+ * the same data as the HTML page, already filtered and ordered, so other programs don't have
+ * to repeat that logic against the API server. CSV is only available for the pages showing the
+ * scores of a hole or a whole tournament.
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Html,
+    Json,
+    Csv,
+}
+
+impl Format {
+    /// The format chosen with `format=` in `query`, if any
+    fn from_query(query: &str) -> Option<Self> {
+        qs::from_str::<Vec<(String, String)>>(query)
+            .ok()?
+            .into_iter()
+            .find(|(k, _)| k == "format")
+            .and_then(|(_, v)| Self::from_code(&v))
+    }
+
+    fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "html" => Some(Self::Html),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    /**
+     * Picks the format for `request`
+     *
+     * `format=` in the query string wins over the `Accept` header. This is also used for the
+     * error pages, so it doesn't rely on the rest of the query string being valid.
+     */
+    pub fn negotiate(request: &Request) -> Self {
+        Self::from_query(&request.query)
+            .or_else(|| request.header("accept").and_then(Self::from_accept))
+            .unwrap_or_default()
+    }
+
+    /// Whichever of `text/html`, `application/json` and `text/csv` comes first in an `Accept` header
+    fn from_accept(header: &str) -> Option<Self> {
+        header.split(',').find_map(|range| {
+            let mut parts = range.split(';');
+            let format = match parts.next()?.trim() {
+                "text/html" => Self::Html,
+                "application/json" => Self::Json,
+                "text/csv" => Self::Csv,
+                _ => return None,
+            };
+            let refused = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .is_some_and(|q| q == 0.0);
+            (!refused).then_some(format)
+        })
+    }
+}
+
 /// The parameters to be collected from the query string
 #[derive(Deserialize)]
 struct QueryParams {
@@ -32,97 +139,242 @@ struct QueryParams {
     tournament: Option<String>,
     #[serde(rename = "h")]
     hole: Option<u8>,
+    view: Option<View>,
+    /// How many scores to show per hole on [`LeaderboardPage`]
+    #[serde(rename = "n")]
+    top: Option<usize>,
+    /// The player to show on [`PlayerPage`]
+    #[serde(rename = "p")]
+    player: Option<String>,
+    /// The member number of the player to show on [`PlayerPage`]
+    #[serde(rename = "m")]
+    member: Option<String>,
+    format: Option<Format>,
 }
 
-impl Params {
-    pub fn new() -> Result<Self, Error> {
-        let query_string = env::var("QUERY_STRING")?;
-        let query_args = qs::from_str(&query_string)?;
-        let server = env::var("SERVER_URL")?;
-        Ok(Params { server, query_args })
-    }
+/// Pages that are chosen with `view=` instead of by which of `u`, `t` and `h` are present
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum View {
+    /// [`LeaderboardPage`]
+    All,
+    /// [`PlayerPage`]
+    Player,
+    /// [`SubmitScorePage`]
+    Submit,
+    /// [`SubmitScorePage`] for changing the score in [`Context::edit`]
+    Edit,
 }
 
-impl TryFrom<Params> for Body {
-    type Error = Error;
+impl Params {
+    pub fn new(
+        request: &Request,
+        server: &str,
+        config: &Config,
+        lang: Lang,
+    ) -> Result<Self, Error> {
+        let query_args: QueryParams = qs::from_str(&request.query)?;
+        let tournament = query_args.tournament.as_deref();
+        if !is_valid_id(&query_args.user) || !tournament.is_none_or(is_valid_id) {
+            return Err(Error::InvalidQueryString);
+        }
+        let backend = backend::open(server, config)?;
+        let edit = Edit::from_request(request, config)?.filter(|_| backend.can_delete());
+        let ctx = Context {
+            backend,
+            lang,
+            unit: Unit::negotiate(request),
+            format: query_args
+                .format
+                .unwrap_or_else(|| Format::negotiate(request)),
+            limits: config.limits(query_args.tournament.as_deref()),
+            edit,
+            member_numbers: config.member_numbers,
+            state: config.state.clone(),
+            secret: config.secret.clone(),
+        };
+        Ok(Params { ctx, query_args })
+    }
 
-    fn try_from(value: Params) -> Result<Self, Self::Error> {
-        let user = value.query_args.user;
-        let tournament = value.query_args.tournament;
-        let hole = value.query_args.hole;
-        let server = value.server;
+    /// Picks the page from the query string and responds with it
+    fn respond(self, request: &Request) -> Result<Response, Error> {
+        let user = self.query_args.user;
+        let tournament = self.query_args.tournament;
+        let hole = self.query_args.hole;
+        let view = self.query_args.view;
+        let ctx = self.ctx;
 
         match tournament {
-            None => SelectTournamentPage { user }.render(&server),
-            Some(tournament) => match hole {
-                None => SelectHolePage { user, tournament }.render(&server),
-                Some(hole) => ViewHolePage {
+            None => respond(SelectTournamentPage { user }, &ctx),
+            Some(tournament) if view == Some(View::All) => respond(
+                LeaderboardPage {
                     user,
                     tournament,
-                    hole,
-                }
-                .render(&server),
+                    top: self.query_args.top.unwrap_or(3),
+                },
+                &ctx,
+            ),
+            Some(tournament) if view == Some(View::Player) => respond(
+                PlayerPage {
+                    user,
+                    tournament,
+                    name: self.query_args.player.ok_or(Error::InvalidQueryString)?,
+                    // Looking players up by member number would show who has which
+                    member: self
+                        .query_args
+                        .member
+                        .filter(|m| !m.trim().is_empty())
+                        .filter(|_| ctx.member_numbers == MemberNumbers::Show),
+                },
+                &ctx,
+            ),
+            Some(tournament) if view == Some(View::Submit) => {
+                let client = csrf::Client::from_request(request)?;
+                let page = SubmitScorePage {
+                    user,
+                    tournament,
+                    hole: hole.ok_or(Error::InvalidQueryString)?,
+                    client: client.id.clone(),
+                    input: CustomScore::default(),
+                    invalid: Vec::new(),
+                    editing: false,
+                };
+                respond(page, &ctx).map(|response| client.set_cookie(response))
+            }
+            Some(tournament) if view == Some(View::Edit) => {
+                let page = ViewHolePage {
+                    user,
+                    tournament,
+                    hole: hole.ok_or(Error::InvalidQueryString)?,
+                };
+                let edit = ctx.edit.as_ref().filter(|e| e.is_for(&page));
+                let edit = edit.ok_or(Error::EditExpired)?;
+                let input = CustomScore::from_score(&edit.score, ctx.limits);
+                let client = csrf::Client::from_request(request)?;
+                let page = SubmitScorePage {
+                    user: page.user,
+                    tournament: page.tournament,
+                    hole: page.hole,
+                    client: client.id.clone(),
+                    input,
+                    invalid: Vec::new(),
+                    editing: true,
+                };
+                respond(page, &ctx).map(|response| client.set_cookie(response))
+            }
+            Some(tournament) => match hole {
+                None => respond(SelectHolePage { user, tournament }, &ctx),
+                Some(hole) => respond(
+                    ViewHolePage {
+                        user,
+                        tournament,
+                        hole,
+                    },
+                    &ctx,
+                ),
             },
         }
     }
 }
 
+/// Responds with `page` in the format asked for in `ctx`
+fn respond(page: impl Render, ctx: &Context) -> Result<Response, Error> {
+    let data = page.data(ctx)?;
+    let response = Response::builder(Status::Ok)
+        .content_language(ctx.lang.code())
+        .header("Vary", "Accept");
+    Ok(match ctx.format {
+        Format::Html => {
+            let content = page.render(data, ctx);
+            response.body(insert_into_template(content, ctx.lang).to_string())
+        }
+        Format::Json => response
+            .content_type(ContentType::Json)
+            .body(serde_json::to_string(&data)?),
+        Format::Csv => {
+            let csv = page.csv(data, ctx).ok_or(Error::InvalidQueryString)?;
+            response
+                .content_type(ContentType::Csv)
+                .attachment(&format!("{}.csv", csv.filename))
+                .body(csv.into_body())
+        }
+    })
+}
+
 /// The page that shows the tournament selection screen
 struct SelectTournamentPage {
     user: String,
 }
 
+/// The tournaments shown on [`SelectTournamentPage`]
+#[derive(Serialize)]
+struct TournamentList {
+    active: Vec<ShortTournament>,
+    finished: Vec<ShortTournament>,
+}
+
 impl ToHtml<ListItem, SelectTournamentPage> for ShortTournament {
-    fn to_html(&self, page: &SelectTournamentPage) -> ListItem {
+    fn to_html(&self, page: &SelectTournamentPage, _: &Context) -> ListItem {
         ListItem::builder()
             .anchor(|a| {
                 a.text(self.tournament_name.to_string())
-                    .href(format!("?u={}&t={}", page.user, self.tournament_id))
+                    .href(escape(&format!(
+                        "?u={}&t={}",
+                        page.user, self.tournament_id
+                    )))
             })
             .build()
     }
 }
 
 impl Render for SelectTournamentPage {
+    type Data = TournamentList;
+
+    /// Tournaments that haven't started or ended more than 3 days ago are left out
+    fn data(&self, ctx: &Context) -> Result<Self::Data, Error> {
+        let tournaments = ctx.backend.tournaments(&self.user)?;
+        let current_time = secs_since_epoch()?;
+
+        let (active, finished) = tournaments
+            .into_iter()
+            .filter(|t| t.t_end >= current_time - 86400 * 3 && t.t_start < current_time)
+            .partition(|t| t.active);
+        Ok(TournamentList { active, finished })
+    }
+
     /**
-     * All 3 of these functions follow this general pattern:
+     * All of the pages follow this general pattern:
      *
      * 1. Define an [`html::root::builders::BodyBuilder`] to create the return value
-     * 2. Fetch the needed data from the API server
-     * 3. One by one create the elements for the page and append them to the `BodyBuilder`
-     * 4. Return the built [`Body`] wrapped in [`Ok`]
+     * 2. One by one create the elements for the page from `data` and append them to the
+     *    `BodyBuilder`
+     * 3. Return the built [`Body`]
      */
-    fn render(&self, server: &str) -> Result<Body, Error> {
+    fn render(&self, data: Self::Data, ctx: &Context) -> Body {
         let mut b = Body::builder();
+        let text = ctx.lang.text();
 
-        let tournaments: Vec<ShortTournament> = Fetch::fetch(server, self)?;
-        let current_time = secs_since_epoch()?;
-
-        b.heading_1(|h1| h1.id("title").text("Vælg en turnering"))
-            .heading_2(|h2| h2.text("Aktive turneringer"));
+        b.heading_1(|h1| h1.id("title").text(text.select_tournament))
+            .heading_2(|h2| h2.text(text.active_tournaments));
 
-        let (active, inactive) = tournaments
-            .into_iter()
-            .filter(|t| t.t_end >= current_time - 86400 * 3 && t.t_start < current_time)
-            .partition(|t| t.active)
-            .map(|vec: Vec<ShortTournament>| {
-                UnorderedList::builder()
-                    .extend(vec.iter().map(|t| t.to_html(self)))
-                    .build()
-            });
+        let (active, finished) = (data.active, data.finished).map(|vec: Vec<ShortTournament>| {
+            UnorderedList::builder()
+                .extend(vec.iter().map(|t| t.to_html(self, ctx)))
+                .build()
+        });
         let no_active_tournaments = active.children().is_empty().then(|| {
             Paragraph::builder()
-                .text("Ingen aktive turneringer")
+                .text(text.no_active_tournaments)
                 .build()
         });
         b.push(active).extend(no_active_tournaments);
 
-        if !inactive.children().is_empty() {
-            b.heading_2(|h2| h2.text("Afsluttede turneringer"))
-                .push(inactive);
+        if !finished.children().is_empty() {
+            b.heading_2(|h2| h2.text(text.finished_tournaments))
+                .push(finished);
         };
 
-        Ok(b.build())
+        b.build()
     }
 }
 
@@ -137,43 +389,94 @@ struct SelectHolePage {
 }
 
 impl ToHtml<ListItem, SelectHolePage> for Hole {
-    fn to_html(&self, page: &SelectHolePage) -> ListItem {
+    fn to_html(&self, page: &SelectHolePage, ctx: &Context) -> ListItem {
         ListItem::builder()
             .anchor(|a| {
-                a.text(format!("Hul {}", self.hole_number)).href(format!(
-                    "?u={}&t={}&h={}",
-                    page.user, page.tournament, self.hole_number
-                ))
+                a.text(format!("{} {}", ctx.lang.text().hole, self.hole_number))
+                    .href(escape(&format!(
+                        "?u={}&t={}&h={}",
+                        page.user, page.tournament, self.hole_number
+                    )))
             })
             .build()
     }
 }
 
 impl Render for SelectHolePage {
+    type Data = Tournament;
+
+    fn data(&self, ctx: &Context) -> Result<Self::Data, Error> {
+        let mut tournament = Tournament::fetch(ctx.backend.as_ref(), &ctx.state, self)?;
+        for hole in &mut tournament.holes {
+            ctx.hide_member_numbers(&mut hole.scores);
+        }
+        Ok(tournament)
+    }
+
+    fn csv(&self, tournament: Self::Data, ctx: &Context) -> Option<Csv> {
+        Some(tournament_csv(tournament, ctx))
+    }
+
     /// See [`SelectTournamentPage::render`]
-    fn render(&self, server: &str) -> Result<Body, Error> {
+    fn render(&self, tournament: Self::Data, ctx: &Context) -> Body {
         let mut b = Body::builder();
-
-        let tournament = Tournament::fetch(server, self)?;
+        let text = ctx.lang.text();
 
         b.heading_1(|h1| h1.id("title").text(tournament.tournament_name));
 
         if !tournament.tournament_sponsor.is_empty() {
-            b.paragraph(|p| p.text(format!("Sponsoreret af: {}", tournament.tournament_sponsor)));
+            b.paragraph(|p| {
+                p.text(format!(
+                    "{} {}",
+                    text.sponsored_by, tournament.tournament_sponsor
+                ))
+            });
         };
 
-        b.heading_2(|h2| h2.text("Vælg et hul"));
+        b.heading_2(|h2| h2.text(text.select_hole));
 
-        let no_holes = tournament.holes.is_empty().then(|| {
-            Paragraph::builder()
-                .text("Der er ingen huller i denne turnering")
-                .build()
-        });
-        let holes = tournament.holes.into_iter().map(|h| h.to_html(self));
+        let no_holes = tournament
+            .holes
+            .is_empty()
+            .then(|| Paragraph::builder().text(text.no_holes).build());
+        let holes = tournament.holes.into_iter().map(|h| h.to_html(self, ctx));
         let holes = UnorderedList::builder().extend(holes).build();
         b.push(holes).extend(no_holes);
 
-        Ok(b.build())
+        b.anchor(|a| {
+            a.text(text.all_holes).href(escape(&format!(
+                "?u={}&t={}&view=all",
+                self.user, self.tournament
+            )))
+        })
+        .paragraph(|p| {
+            p.anchor(|a| {
+                a.text(text.download_csv).href(escape(&format!(
+                    "?u={}&t={}&format=csv",
+                    self.user, self.tournament
+                )))
+            })
+        });
+
+        let (user, tournament) = (self.user.clone(), self.tournament.clone());
+        b.heading_2(|h2| h2.text(text.find_player)).form(|form| {
+            form.method("get")
+                .input(|i| i.type_("hidden").name("u").value(escape(&user)))
+                .input(|i| i.type_("hidden").name("t").value(escape(&tournament)))
+                .input(|i| i.type_("hidden").name("view").value("player"))
+                .paragraph(|p| {
+                    p.label(|l| l.for_("player").text(text.name)).input(|i| {
+                        i.id("player")
+                            .name("p")
+                            .type_("search")
+                            .autocomplete("name")
+                            .required("")
+                    })
+                })
+                .input(|i| i.type_("submit").value(text.search))
+        });
+
+        b.build()
     }
 }
 
@@ -193,64 +496,591 @@ pub struct ViewHolePage {
     pub hole: u8,
 }
 
-impl ToHtml<TableRow, ()> for (usize, Score) {
-    fn to_html(&self, _: &()) -> TableRow {
+/// The hole shown on [`ViewHolePage`]
+#[derive(Serialize)]
+struct HoleView {
+    #[serde(flatten)]
+    hole: Hole,
+    tournament_name: String,
+    /// Whether scores can still be submitted to the tournament
+    active: bool,
+}
+
+/// A row of [`score_table`], `rank` is 0-based
+fn score_row(rank: usize, score: &Score, member_column: bool, ctx: &Context) -> TableRow {
+    let member = member_column.then(|| {
+        let member = score.member_number().unwrap_or_default();
+        TableCell::builder().text(escape(member)).build()
+    });
+    TableRow::builder()
+        .table_cell(|td| td.text(format!("{}.", rank + 1)))
+        .table_cell(|td| {
+            td.text(escape(score.bare_name()))
+                .extend(not_leading_marker(score, ctx))
+        })
+        .extend(member)
+        .table_cell(|td| td.text(distance(score.player_score, ctx)))
+        .build()
+}
+
+/**
+ * Shown after the names of scores that were submitted while not leading
+ *
+ * The flag is drawn by the stylesheet, screen readers read the label instead
+ */
+fn not_leading_marker(score: &Score, ctx: &Context) -> Option<Span> {
+    let label = ctx.lang.text().not_leading;
+    score.not_leading.then(|| {
+        Span::builder()
+            .class("not-leading")
+            .title(label)
+            .span(|s| s.class("visually-hidden").text(label))
+            .build()
+    })
+}
+
+impl Render for ViewHolePage {
+    type Data = HoleView;
+
+    fn data(&self, ctx: &Context) -> Result<Self::Data, Error> {
+        let mut hole = Hole::fetch(ctx.backend.as_ref(), &ctx.state, self)?;
+        ctx.hide_member_numbers(&mut hole.scores);
+        let tournament = self.short_tournament(ctx.backend.as_ref())?;
+        Ok(HoleView {
+            hole,
+            active: tournament.as_ref().is_some_and(|t| t.active),
+            tournament_name: tournament
+                .map(|t| t.tournament_name)
+                .unwrap_or_else(|| self.tournament.clone()),
+        })
+    }
+
+    fn csv(&self, data: Self::Data, ctx: &Context) -> Option<Csv> {
+        let filename = format!("{} - {}", data.tournament_name, data.hole.title(ctx));
+        let mut csv = Csv::new(filename, ctx.lang);
+        csv.row(csv_header(ctx));
+        for (rank, score) in data.hole.scores.iter().enumerate() {
+            csv.row(csv_fields(rank, score, ctx));
+        }
+        Some(csv)
+    }
+
+    /// See [`SelectTournamentPage::render`]
+    fn render(&self, data: Self::Data, ctx: &Context) -> Body {
+        let mut b = Body::builder();
+        let text = ctx.lang.text();
+        let hole = data.hole;
+
+        b.heading_1(|h1| h1.id("title").text(hole.title(ctx)));
+
+        if !hole.hole_sponsor.is_empty() {
+            b.paragraph(|p| p.text(format!("{} {}", text.sponsored_by, hole.hole_sponsor)));
+        };
+
+        b.push(score_table(hole.scores, ctx));
+
+        if ctx.edit.as_ref().is_some_and(|e| e.is_for(self)) && data.active {
+            let href = format!(
+                "?u={}&t={}&h={}&view=edit",
+                self.user, self.tournament, self.hole
+            );
+            b.paragraph(|p| p.anchor(|a| a.text(text.change_score).href(escape(&href))));
+        }
+
+        let submit = data.active.then(|| {
+            let href = format!(
+                "?u={}&t={}&h={}&view=submit",
+                self.user, self.tournament, hole.hole_number
+            );
+            Anchor::builder()
+                .text(text.submit_score)
+                .href(escape(&href))
+                .build()
+        });
+        b.extend(submit).paragraph(|p| {
+            p.anchor(|a| {
+                a.text(text.download_csv).href(escape(&format!(
+                    "?u={}&t={}&h={}&format=csv",
+                    self.user, self.tournament, hole.hole_number
+                )))
+            })
+        });
+
+        b.build()
+    }
+}
+
+/**
+ * The page with the form for submitting a score to a hole
+ *
+ * The hole is put in the query string of the form's action. [`crate::post`] also shows it again with the player's `input` if some of
+ * the fields are `invalid`.
+ * When `editing` it changes or withdraws the score in [`Context::edit`] instead.
+ */
+pub struct SubmitScorePage {
+    pub user: String,
+    pub tournament: String,
+    pub hole: u8,
+    /// The id of the [`csrf::Client`] the form is shown to
+    pub client: String,
+    pub input: CustomScore,
+    pub invalid: Vec<Field>,
+    pub editing: bool,
+}
+
+impl SubmitScorePage {
+    /// Responds with the form again, after a score with `invalid` fields was submitted
+    pub fn respond_invalid(self, ctx: &Context) -> Result<Response, Error> {
+        let data = self.data(ctx)?;
+        let content = self.render(data, ctx);
+        Ok(Response::builder(Status::UnprocessableContent)
+            .content_language(ctx.lang.code())
+            .cache_control("no-store")
+            .body(insert_into_template(content, ctx.lang).to_string()))
+    }
+
+    /// The message shown next to `field` if it's invalid
+    fn error(&self, field: Field, ctx: &Context) -> Option<Span> {
+        self.invalid.contains(&field).then(|| {
+            Span::builder()
+                .class("error")
+                .text(field.message(ctx.lang, ctx.limits))
+                .build()
+        })
+    }
+}
+
+impl From<&SubmitScorePage> for ViewHolePage {
+    fn from(value: &SubmitScorePage) -> Self {
+        Self {
+            user: value.user.clone(),
+            tournament: value.tournament.clone(),
+            hole: value.hole,
+        }
+    }
+}
+
+/// The hole shown on [`SubmitScorePage`]
+#[derive(Serialize)]
+struct SubmitForm {
+    #[serde(flatten)]
+    hole: HoleView,
+    /// Has to be submitted with the score, see [`crate::csrf`]
+    token: String,
+    /// See [`CustomScore::key`]
+    key: String,
+}
+
+impl Render for SubmitScorePage {
+    type Data = SubmitForm;
+
+    /// Fails with [`Error::TournamentClosed`] if scores can't be submitted anymore
+    fn data(&self, ctx: &Context) -> Result<Self::Data, Error> {
+        let page = ViewHolePage::from(self);
+        let hole = page.data(ctx)?;
+        if !hole.active {
+            return Err(Error::TournamentClosed);
+        }
+        let token = csrf::token(&page, &self.client, &ctx.secret)?;
+        // Kept when the form is shown again, so it's still the same submission
+        let key = match is_key(&self.input.key) {
+            true => self.input.key.clone(),
+            false => csrf::random_hex(16)?,
+        };
+        Ok(SubmitForm { hole, token, key })
+    }
+
+    /// See [`SelectTournamentPage::render`]
+    fn render(&self, data: Self::Data, ctx: &Context) -> Body {
+        let mut b = Body::builder();
+        let text = ctx.lang.text();
+        let (hole, token, key) = (data.hole.hole, data.token, data.key);
+        let title = match self.editing {
+            true => text.edit_score,
+            false => text.submit_score,
+        };
+
+        b.heading_1(|h1| h1.id("title").text(title))
+            .paragraph(|p| p.text(data.hole.tournament_name))
+            .heading_2(|h2| h2.text(hole.title(ctx)));
+
+        if !hole.hole_sponsor.is_empty() {
+            b.paragraph(|p| p.text(format!("{} {}", text.sponsored_by, hole.hole_sponsor)));
+        };
+
+        let action = format!("?u={}&t={}&h={}", self.user, self.tournament, self.hole);
+        let input = &self.input;
+        let limits = ctx.limits;
+        let edit = ctx.edit.as_ref().filter(|_| self.editing).map(|edit| {
+            Input::builder()
+                .type_("hidden")
+                .name("edit")
+                .value(edit.id.clone())
+                .build()
+        });
+        let withdraw = self.editing.then(|| {
+            Input::builder()
+                .type_("submit")
+                .name("withdraw")
+                .value(text.withdraw)
+                .formnovalidate("")
+                .build()
+        });
+        b.form(|form| {
+            form.action(escape(&action))
+                .method("post")
+                .input(|i| i.type_("hidden").name("token").value(token))
+                .input(|i| i.type_("hidden").name("key").value(key))
+                .extend(edit)
+                .paragraph(|p| {
+                    p.label(|l| l.for_("name").text(text.name))
+                        .input(|i| {
+                            i.id("name")
+                                .name("name")
+                                .type_("text")
+                                .autocomplete("name")
+                                .maxlength("40")
+                                .minlength("2")
+                                .required("")
+                                .value(escape(&input.name))
+                        })
+                        .extend(self.error(Field::Name, ctx))
+                })
+                .paragraph(|p| {
+                    p.label(|l| {
+                        l.text(text.member_number)
+                            .for_("member")
+                            .super_script(|sup| sup.text(text.optional))
+                    })
+                    .input(|i| {
+                        i.id("member")
+                            .name("member")
+                            .type_("text")
+                            .maxlength("11")
+                            .minlength("2")
+                            .pattern("\\d*[\\- ]?\\d*")
+                            .size("11")
+                            .value(escape(&input.member))
+                    })
+                    .extend(self.error(Field::Member, ctx))
+                })
+                .fieldset(|fieldset| {
+                    fieldset
+                        .legend(|legend| legend.text(text.distance))
+                        .input(|i| {
+                            i.id("m")
+                                .name("score_m")
+                                .type_("number")
+                                .min("0")
+                                .max(limits.max_meters.to_string())
+                                .step("1")
+                                .required("")
+                                .value(escape(&input.score_m))
+                        })
+                        .label(|l| l.for_("m").text("m"))
+                        .input(|i| {
+                            i.id("cm")
+                                .name("score_cm")
+                                .type_("number")
+                                .min("0")
+                                .max(limits.max_cm().to_string())
+                                .step(limits.resolution_cm.to_string())
+                                .required("")
+                                .value(escape(&input.score_cm))
+                        })
+                        .label(|l| l.for_("cm").text("cm"))
+                        .extend(self.error(Field::ScoreM, ctx))
+                        .extend(self.error(Field::ScoreCm, ctx))
+                })
+                .input(|i| i.type_("submit").value(text.submit))
+                .extend(withdraw)
+        });
+
+        b.build()
+    }
+}
+
+/// The headings of the columns from [`csv_fields`]
+fn csv_header(ctx: &Context) -> Vec<String> {
+    let text = ctx.lang.text();
+    let member = (ctx.member_numbers == MemberNumbers::Show).then_some(text.member_number);
+    [text.rank, text.name]
+        .into_iter()
+        .chain(member)
+        .map(str::to_owned)
+        .chain([format!("{} (m)", text.distance)])
+        .collect()
+}
+
+/// The rank, name, member number (unless hidden) and distance of `score`, for a CSV file
+fn csv_fields(rank: usize, score: &Score, ctx: &Context) -> Vec<String> {
+    let member = (ctx.member_numbers == MemberNumbers::Show)
+        .then(|| score.member_number().unwrap_or_default().to_owned());
+    [(rank + 1).to_string(), score.bare_name().to_owned()]
+        .into_iter()
+        .chain(member)
+        .chain([format::decimal(
+            score.player_score,
+            ctx.limits.decimals(),
+            ctx.lang,
+        )])
+        .collect()
+}
+
+/// The CSV with every score in `tournament`, one row per hole per score
+fn tournament_csv(tournament: Tournament, ctx: &Context) -> Csv {
+    let text = ctx.lang.text();
+    let mut csv = Csv::new(tournament.tournament_name, ctx.lang);
+    csv.row([text.hole.to_owned()].into_iter().chain(csv_header(ctx)));
+    for hole in tournament.holes {
+        let title = hole.title(ctx);
+        for (rank, score) in hole.scores.iter().enumerate() {
+            csv.row(
+                [title.clone()]
+                    .into_iter()
+                    .chain(csv_fields(rank, score, ctx)),
+            );
+        }
+    }
+    csv
+}
+
+/// Formats a distance for `ctx`
+fn distance(meters: f64, ctx: &Context) -> String {
+    format::distance(meters, ctx.limits.decimals(), ctx.lang, ctx.unit)
+}
+
+/**
+ * The table of scores on a hole
+ *
+ * Member numbers get their own column if any of the scores have one, see
+ * [`Context::hide_member_numbers`]
+ */
+fn score_table(scores: Vec<Score>, ctx: &Context) -> Table {
+    let text = ctx.lang.text();
+    let member_column = scores.iter().any(|s| s.member_number().is_some());
+    let member_label = member_column.then_some(text.member_number);
+    let thead_labels = [text.rank, text.name]
+        .into_iter()
+        .chain(member_label)
+        .chain([text.score])
+        .map(|l| TableHeader::builder().text(l).scope("col").build());
+    let thead = TableHead::builder()
+        .table_row(|tr| tr.extend(thead_labels))
+        .build();
+    let columns = if member_column { "4" } else { "3" };
+    let no_scores = scores.is_empty().then(|| {
         TableRow::builder()
-            .table_cell(|td| td.text(format!("{}.", self.0 + 1)))
-            .table_cell(|td| td.text(self.1.player_name.clone()))
-            .table_cell(|td| td.text(format!("{:.2}m", self.1.player_score,).replacen('.', ",", 1)))
+            .table_cell(|td| td.text(text.no_scores).colspan(columns))
             .build()
+    });
+    let scores = scores
+        .iter()
+        .enumerate()
+        .map(|(rank, score)| score_row(rank, score, member_column, ctx));
+    let tbody = TableBody::builder()
+        .extend(scores)
+        .extend(no_scores)
+        .build();
+    Table::builder().push(thead).push(tbody).build()
+}
+
+/**
+ * The page that shows the best scores on every hole in a tournament
+ *
+ * Meant for reading out the winners at the prize-giving
+ */
+struct LeaderboardPage {
+    user: String,
+    tournament: String,
+    /// How many scores to show per hole
+    top: usize,
+}
+
+impl From<&LeaderboardPage> for SelectHolePage {
+    fn from(value: &LeaderboardPage) -> Self {
+        Self {
+            user: value.user.clone(),
+            tournament: value.tournament.clone(),
+        }
     }
 }
 
-impl Render for ViewHolePage {
+impl Render for LeaderboardPage {
+    type Data = Tournament;
+
+    /// Only the best `top` scores are kept on every hole
+    fn data(&self, ctx: &Context) -> Result<Self::Data, Error> {
+        let mut tournament = Tournament::fetch(ctx.backend.as_ref(), &ctx.state, &self.into())?;
+        for hole in &mut tournament.holes {
+            hole.scores.truncate(self.top);
+            ctx.hide_member_numbers(&mut hole.scores);
+        }
+        Ok(tournament)
+    }
+
+    fn csv(&self, tournament: Self::Data, ctx: &Context) -> Option<Csv> {
+        Some(tournament_csv(tournament, ctx))
+    }
+
     /// See [`SelectTournamentPage::render`]
-    fn render(&self, server: &str) -> Result<Body, Error> {
+    fn render(&self, tournament: Self::Data, ctx: &Context) -> Body {
         let mut b = Body::builder();
+        let text = ctx.lang.text();
+
+        b.heading_1(|h1| h1.id("title").text(tournament.tournament_name));
 
-        let hole = Hole::fetch(server, self)?;
+        if !tournament.tournament_sponsor.is_empty() {
+            b.paragraph(|p| {
+                p.text(format!(
+                    "{} {}",
+                    text.sponsored_by, tournament.tournament_sponsor
+                ))
+            });
+        };
 
-        let title = hole
-            .hole_text
+        let no_holes = tournament
+            .holes
             .is_empty()
-            .then(|| format!("Hul {}", hole.hole_number))
-            .unwrap_or(hole.hole_text);
-        b.heading_1(|h1| h1.id("title").text(title));
+            .then(|| Paragraph::builder().text(text.no_holes).build());
+        for hole in tournament.holes {
+            let href = format!(
+                "?u={}&t={}&h={}",
+                self.user, self.tournament, hole.hole_number
+            );
+            let title = hole.title(ctx);
+            b.heading_2(|h2| h2.anchor(|a| a.text(title).href(escape(&href))));
+            b.push(score_table(hole.scores, ctx));
+        }
+        b.extend(no_holes);
 
-        if !hole.hole_sponsor.is_empty() {
-            b.paragraph(|p| p.text(format!("Sponsoreret af: {}", hole.hole_sponsor)));
+        b.build()
+    }
+}
+
+/**
+ * The page that shows every score a single player has on the holes of a tournament
+ *
+ * Players are matched by name, ignoring case and the member number, unless `member` is given.
+ * `m=` is ignored when member numbers are hidden
+ */
+struct PlayerPage {
+    user: String,
+    tournament: String,
+    name: String,
+    member: Option<String>,
+}
+
+/// The scores shown on [`PlayerPage`]
+#[derive(Serialize)]
+struct PlayerScores {
+    name: String,
+    /// Left out when member numbers are hidden, see [`PlayerPage`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    member: Option<String>,
+    tournament_name: String,
+    scores: Vec<PlayerScore>,
+}
+
+/// One of the scores on [`PlayerPage`], with the hole it was made on
+#[derive(Serialize)]
+struct PlayerScore {
+    hole_number: u8,
+    hole_text: String,
+    /// 1-based
+    rank: usize,
+    #[serde(flatten, serialize_with = "public")]
+    score: Score,
+}
+
+impl From<&PlayerPage> for SelectHolePage {
+    fn from(value: &PlayerPage) -> Self {
+        Self {
+            user: value.user.clone(),
+            tournament: value.tournament.clone(),
+        }
+    }
+}
+
+impl PlayerPage {
+    fn matches(&self, score: &Score) -> bool {
+        let member_matches = match (&self.member, score.member_number()) {
+            (None, _) => true,
+            (Some(wanted), Some(member)) => normalize(wanted) == normalize(member),
+            (Some(_), None) => false,
+        };
+        member_matches && normalize(&self.name) == normalize(score.bare_name())
+    }
+}
+
+impl Render for PlayerPage {
+    type Data = PlayerScores;
+
+    fn data(&self, ctx: &Context) -> Result<Self::Data, Error> {
+        let tournament = Tournament::fetch(ctx.backend.as_ref(), &ctx.state, &self.into())?;
+
+        let mut scores = Vec::new();
+        for hole in tournament.holes {
+            for (rank, score) in hole.scores.into_iter().enumerate() {
+                if self.matches(&score) {
+                    let mut score = score;
+                    ctx.hide_member_numbers(std::slice::from_mut(&mut score));
+                    scores.push(PlayerScore {
+                        hole_number: hole.hole_number,
+                        hole_text: hole.hole_text.clone(),
+                        rank: rank + 1,
+                        score,
+                    });
+                }
+            }
+        }
+
+        Ok(PlayerScores {
+            name: self.name.trim().to_owned(),
+            member: self.member.as_ref().map(|m| m.trim().to_owned()),
+            tournament_name: tournament.tournament_name,
+            scores,
+        })
+    }
+
+    /// See [`SelectTournamentPage::render`]
+    fn render(&self, data: Self::Data, ctx: &Context) -> Body {
+        let mut b = Body::builder();
+        let text = ctx.lang.text();
+
+        let title = match &data.member {
+            Some(member) => format!("{} ({member})", data.name),
+            None => data.name,
         };
+        b.heading_1(|h1| h1.id("title").text(escape(&title)))
+            .paragraph(|p| p.text(data.tournament_name));
 
-        let thead_labels =
-            ["Nr.", "Navn", "Score"].map(|l| TableHeader::builder().text(l).scope("col").build());
+        let thead_labels = [text.hole, text.rank, text.score]
+            .map(|l| TableHeader::builder().text(l).scope("col").build());
         let thead = TableHead::builder()
             .table_row(|tr| tr.extend(thead_labels))
             .build();
-        let no_scores = hole.scores.is_empty().then(|| {
+
+        let rows = data.scores.iter().map(|s| {
+            let title = hole_title(s.hole_number, &s.hole_text, ctx);
+            let href = format!("?u={}&t={}&h={}", self.user, self.tournament, s.hole_number);
+            let distance = distance(s.score.player_score, ctx);
             TableRow::builder()
-                .table_cell(|td| td.text("Der er ingen noteringer endnu").colspan("3"))
+                .table_cell(|td| td.anchor(|a| a.text(title).href(escape(&href))))
+                .table_cell(|td| td.text(format!("{}.", s.rank)))
+                .table_cell(|td| td.text(distance))
                 .build()
         });
-        let scores = hole.scores.into_iter().enumerate().map(|s| s.to_html(&()));
-        let tbody = TableBody::builder()
-            .extend(scores)
-            .extend(no_scores)
-            .build();
-        b.table(|table| table.push(thead).push(tbody));
 
-        let submit = self.active(server).map(|_| {
-            let href = format!(
-                "/submit_score.html?u={}&t={}&h={}",
-                self.user, self.tournament, hole.hole_number
-            );
-            Anchor::builder()
-                .text("Indsend notering")
-                .href(href)
+        let no_scores = data.scores.is_empty().then(|| {
+            TableRow::builder()
+                .table_cell(|td| td.text(text.no_player_scores).colspan("3"))
                 .build()
         });
-        b.extend(submit);
+        let tbody = TableBody::builder().extend(rows).extend(no_scores).build();
+        b.table(|table| table.push(thead).push(tbody));
 
-        Ok(b.build())
+        b.build()
     }
 }
 
@@ -263,13 +1093,21 @@ impl From<&ViewHolePage> for SelectTournamentPage {
 }
 
 impl ViewHolePage {
-    fn active(&self, server: &str) -> Result<bool, Error> {
-        let tournament_list: Vec<ShortTournament> = Fetch::fetch(server, &self.into())?;
+    /**
+     * Whether the user and tournament can safely be put in the path of an API URL
+     *
+     * Used for the hole a score is submitted to, which comes from the form
+     */
+    pub fn is_valid(&self) -> bool {
+        is_valid_id(&self.user) && is_valid_id(&self.tournament)
+    }
+
+    /// The tournament the hole is in, as it's listed on [`SelectTournamentPage`]
+    fn short_tournament(&self, backend: &dyn Backend) -> Result<Option<ShortTournament>, Error> {
+        let tournament_list = backend.tournaments(&self.user)?;
         Ok(tournament_list
             .into_iter()
-            .find(|t| t.tournament_id == self.tournament)
-            .map(|t| t.active)
-            .unwrap_or(false))
+            .find(|t| t.tournament_id == self.tournament))
     }
 }
 
@@ -278,8 +1116,8 @@ impl ViewHolePage {
  *
  * <https://github.com/itzgoldenleonard/BullseyeGolf-server/blob/main/openapi.yaml#L307>
  */
-#[derive(Deserialize)]
-struct ShortTournament {
+#[derive(Deserialize, Serialize)]
+pub struct ShortTournament {
     active: bool,
     t_start: u64,
     t_end: u64,
@@ -287,22 +1125,13 @@ struct ShortTournament {
     tournament_name: String,
 }
 
-impl Fetch for Vec<ShortTournament> {
-    type Page = SelectTournamentPage;
-
-    fn fetch(server: &str, page: &Self::Page) -> Result<Self, Error> {
-        let url = format!("{server}/{}", page.user);
-        Ok(http::get(url)?.error_for_status()?.json()?)
-    }
-}
-
 /**
  * See the definition at:
  *
  * <https://github.com/itzgoldenleonard/BullseyeGolf-server/blob/main/openapi.yaml#L275>
  */
-#[derive(Deserialize)]
-struct Tournament {
+#[derive(Deserialize, Serialize)]
+pub struct Tournament {
     tournament_name: String,
     /// Optional
     tournament_sponsor: String,
@@ -310,18 +1139,19 @@ struct Tournament {
     holes: Vec<Hole>,
 }
 
-impl Fetch for Tournament {
-    type Page = SelectHolePage;
-
-    fn fetch(server: &str, page: &Self::Page) -> Result<Self, Error> {
-        let url = format!("{server}/{}/{}", page.user, page.tournament);
-        let client = http::Client::new();
-        Ok(client
-            .get(url)
-            .header("No-Hole-Images", "true")
-            .send()?
-            .error_for_status()?
-            .json()?)
+impl Tournament {
+    /// Fetches the tournament `page` is about, with [`Score::not_leading`] set from `state`
+    fn fetch(backend: &dyn Backend, state: &Store, page: &SelectHolePage) -> Result<Self, Error> {
+        let mut tournament = backend.tournament(&page.user, &page.tournament)?;
+        for hole in &mut tournament.holes {
+            let page = ViewHolePage {
+                user: page.user.clone(),
+                tournament: page.tournament.clone(),
+                hole: hole.hole_number,
+            };
+            not_leading::mark(state, &page, &mut hole.scores);
+        }
+        Ok(tournament)
     }
 }
 
@@ -330,46 +1160,159 @@ impl Fetch for Tournament {
  *
  * <https://github.com/itzgoldenleonard/BullseyeGolf-server/blob/main/openapi.yaml#L242>
  */
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct Hole {
     hole_number: u8,
     /// Optional
     hole_text: String,
     /// Optional
     hole_sponsor: String,
-    /// Optional
+    /// Optional, ordered from best to worst
+    #[serde(serialize_with = "ranked")]
     pub scores: Vec<Score>,
 }
 
+/// Serializes `scores` with the 1-based rank they're shown with on the pages
+fn ranked<S: Serializer>(scores: &[Score], serializer: S) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct RankedScore<'a> {
+        rank: usize,
+        #[serde(flatten, serialize_with = "public")]
+        score: &'a Score,
+    }
+
+    serializer.collect_seq(
+        scores
+            .iter()
+            .enumerate()
+            .map(|(i, score)| RankedScore { rank: i + 1, score }),
+    )
+}
+
+/// Serializes `score` for the JSON pages, with the member number apart from the name
+fn public<S: Serializer>(score: &Score, serializer: S) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct PublicScore<'a> {
+        player_name: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        member_number: Option<&'a str>,
+        player_score: f64,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        not_leading: bool,
+    }
+
+    PublicScore {
+        player_name: score.bare_name(),
+        member_number: score.member_number(),
+        player_score: score.player_score,
+        not_leading: score.not_leading,
+    }
+    .serialize(serializer)
+}
+
 /**
  * See the definition at:
  *
  * <https://github.com/itzgoldenleonard/BullseyeGolf-server/blob/main/openapi.yaml#L226>
+ *
+ * The API server has no field for the member number, so [`crate::post`] puts it in front of the
+ * name and [`Score::member_number`] splits it off again
  */
-#[derive(Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Score {
     pub player_name: String,
     pub player_score: f64,
+    /// Submitted while another score was better, not stored by the API server, see
+    /// [`crate::not_leading`]
+    #[serde(skip)]
+    pub not_leading: bool,
+}
+
+impl PartialEq for Score {
+    /// The same score on the API server, which doesn't know about [`Score::not_leading`]
+    fn eq(&self, other: &Self) -> bool {
+        self.player_name == other.player_name && self.player_score == other.player_score
+    }
 }
 
-impl Fetch for Hole {
-    type Page = ViewHolePage;
+impl Score {
+    /**
+     * The member number that [`crate::post`] puts in front of the name, if there is one
+     *
+     * Member numbers are digits, optionally split in two by a '-' or a space, and 2 to 11
+     * characters long like the form requires, so a name like "2 Putt" is left alone
+     */
+    pub fn member_number(&self) -> Option<&str> {
+        split_member_number(&self.player_name).0
+    }
 
-    fn fetch(server: &str, page: &Self::Page) -> Result<Self, Error> {
-        let url = format!("{server}/{}/{}/{}", page.user, page.tournament, page.hole);
-        Ok(http::get(url)?.error_for_status()?.json()?)
+    /// The name without the member number
+    pub fn bare_name(&self) -> &str {
+        split_member_number(&self.player_name).1
     }
 }
 
-/** 
- * Shared HTML for all 3 pages
+/**
+ * The member number at the start of `player_name` and the rest, see [`Score::member_number`]
+ *
+ * Two groups of digits are tried before only the first one, so "55 10 Pins" is member number
+ * "55 10", but [`crate::post`] can make it "55" with a no-break space after the "10".
+ */
+pub fn split_member_number(player_name: &str) -> (Option<&str>, &str) {
+    let digits = |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    let first = digits(player_name);
+    if first == 0 {
+        return (None, player_name);
+    }
+    let second = player_name[first..]
+        .strip_prefix(['-', ' '])
+        .map(digits)
+        .filter(|more| *more > 0)
+        .map(|more| first + 1 + more);
+    for end in second.into_iter().chain([first]) {
+        let member = &player_name[..end];
+        match player_name[end..].strip_prefix(' ') {
+            Some(name) if is_member_number(member) && !name.trim().is_empty() => {
+                return (Some(member), name)
+            }
+            _ => {}
+        }
+    }
+    (None, player_name)
+}
+
+impl Hole {
+    /// Fetches the hole `page` is about, with [`Score::not_leading`] set from `state`
+    pub fn fetch(backend: &dyn Backend, state: &Store, page: &ViewHolePage) -> Result<Self, Error> {
+        let mut hole = backend.hole(page)?;
+        not_leading::mark(state, page, &mut hole.scores);
+        Ok(hole)
+    }
+
+    fn title(&self, ctx: &Context) -> String {
+        hole_title(self.hole_number, &self.hole_text, ctx)
+    }
+}
+
+/// The `hole_text` if there is one, otherwise the hole number
+fn hole_title(hole_number: u8, hole_text: &str, ctx: &Context) -> String {
+    if hole_text.is_empty() {
+        format!("{} {}", ctx.lang.text().hole, hole_number)
+    } else {
+        hole_text.to_owned()
+    }
+}
+
+/**
+ * Shared HTML for all the pages
  *
  * This function inserts the [`Body`], which is the only part that
  * differs between the pages into the template
  */
-fn insert_into_template(content: Body) -> Html {
+fn insert_into_template(content: Body, lang: Lang) -> Html {
     Html::builder()
-        .lang("da")
+        .lang(lang.code())
         .head(|head| {
             head.meta(|meta| meta.charset("utf-8"))
                 .meta(|meta| {
@@ -384,18 +1327,100 @@ fn insert_into_template(content: Body) -> Html {
         .build()
 }
 
-/// Renders the page as HTML
-trait Render {
-    fn render(&self, server: &str) -> Result<Body, Error>;
+/**
+ * Whether a user or tournament id can safely be put in the path of an API URL
+ *
+ * Anything else, like a `#` or a `/`, would make the API URL point somewhere other than the
+ * page links do.
+ */
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_.~".contains(c))
+        && id != "."
+        && id != ".."
 }
 
-/// Fetches data from the API corresponding to what's needed for [`Fetch::Page`]
-pub trait Fetch: Sized {
-    type Page;
-    fn fetch(server: &str, page: &Self::Page) -> Result<Self, Error>;
+/// Escapes text from users so it can be put in HTML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/**
+ * A page that can be sent as either HTML or JSON, see [`Format`]
+ *
+ * [`Render::Data`] is everything shown on the page, it is what the JSON looks like
+ */
+trait Render {
+    type Data: Serialize;
+    /// Fetches the data from the [`Backend`] and filters and orders it like the page shows it
+    fn data(&self, ctx: &Context) -> Result<Self::Data, Error>;
+    /// Renders `data` as HTML
+    fn render(&self, data: Self::Data, ctx: &Context) -> Body;
+    /// Renders `data` as CSV, only the pages with scores from a hole or a tournament can
+    fn csv(&self, _data: Self::Data, _ctx: &Context) -> Option<Csv> {
+        None
+    }
 }
 
 /// Converts `self` to a sensible HTML element `T` to be shown on the page `P`
 trait ToHtml<T, P> {
-    fn to_html(&self, page: &P) -> T;
+    fn to_html(&self, page: &P, ctx: &Context) -> T;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::JsonFile;
+    use serde_json::json;
+
+    /// A page rendered straight from tournaments in memory, without a file or an API server
+    #[test]
+    fn pages_render_from_a_value() {
+        let state = Store::new(std::env::temp_dir().join(format!(
+            "bullseyegolf-light-{}/pages_render_from_a_value",
+            std::process::id()
+        )));
+        let backend = JsonFile::from_value(json!({ "club": { "summer": {
+            "tournament_name": "Summer Cup", "tournament_sponsor": "", "active": true,
+            "t_start": 1700000000u64, "t_end": 4102444800u64,
+            "holes": [{ "hole_number": 7, "hole_text": "", "hole_sponsor": "", "scores": [
+                { "player_name": "55 Dora", "player_score": 3.05 }
+            ] }]
+        } } }));
+        let ctx = Context {
+            backend: Box::new(backend),
+            lang: Lang::En,
+            unit: Unit::Meters,
+            format: Format::Html,
+            limits: Limits::default(),
+            edit: None,
+            member_numbers: MemberNumbers::Hide,
+            secret: Secret::from_env(state.clone()),
+            state,
+        };
+        let page = ViewHolePage {
+            user: "club".to_owned(),
+            tournament: "summer".to_owned(),
+            hole: 7,
+        };
+        let score = Score {
+            player_name: "Egon".to_owned(),
+            player_score: 1.5,
+            not_leading: false,
+        };
+        ctx.backend.submit(&page, &score).unwrap();
+
+        let mut http = Vec::new();
+        respond(page, &ctx).unwrap().write_http(&mut http).unwrap();
+        let http = String::from_utf8(http).unwrap();
+        assert!(http.starts_with("HTTP/1.1 200"), "{http}");
+        assert!(http.contains("Hole 7"));
+        assert!(http.find("Egon").unwrap() < http.find("Dora").unwrap());
+        assert!(!http.contains("55 Dora"));
+    }
 }
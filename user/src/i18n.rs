@@ -0,0 +1,244 @@
+use crate::request::Request;
+use serde::Deserialize;
+use serde_urlencoded as qs;
+
+/**
+ * The languages the user interface is available in
+ *
+ * Danish is the default since that's what most of the clubs speak
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    Da,
+    En,
+}
+
+impl Lang {
+    /// The BCP 47 language tag, used for the `lang` attribute and `Content-Language`
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Da => "da",
+            Self::En => "en",
+        }
+    }
+
+    pub fn decimal_separator(self) -> char {
+        match self {
+            Self::Da => ',',
+            Self::En => '.',
+        }
+    }
+
+    pub fn text(self) -> &'static Catalogue {
+        match self {
+            Self::Da => &DA,
+            Self::En => &EN,
+        }
+    }
+
+    fn from_code(code: &str) -> Option<Self> {
+        let primary = code.split('-').next()?.trim();
+        [Self::Da, Self::En]
+            .into_iter()
+            .find(|l| l.code().eq_ignore_ascii_case(primary))
+    }
+
+    /// The language explicitly chosen with `lang=` in the query string
+    pub fn from_query(query: &str) -> Option<Self> {
+        qs::from_str::<Vec<(String, String)>>(query)
+            .ok()?
+            .into_iter()
+            .find(|(k, _)| k == "lang")
+            .and_then(|(_, v)| Self::from_code(&v))
+    }
+
+    /**
+     * Picks the language for `request`
+     *
+     * In order of priority: `lang=` in the query string, the `lang` cookie (which is set when
+     * `lang=` is used, so the choice sticks between pages), and the `Accept-Language` header
+     */
+    pub fn negotiate(request: &Request) -> Self {
+        Self::from_query(&request.query)
+            .or_else(|| request.cookie("lang").and_then(Self::from_code))
+            .or_else(|| {
+                request
+                    .header("accept-language")
+                    .and_then(Self::from_accept)
+            })
+            .unwrap_or_default()
+    }
+
+    /// The supported language with the highest quality value in an `Accept-Language` header
+    fn from_accept(header: &str) -> Option<Self> {
+        let mut best: Option<(Self, f32)> = None;
+        for range in header.split(',') {
+            let mut parts = range.split(';');
+            let Some(lang) = parts.next().and_then(Self::from_code) else {
+                continue;
+            };
+            let quality = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse().ok())
+                .unwrap_or(1.0);
+            if quality > 0.0 && best.is_none_or(|(_, q)| quality > q) {
+                best = Some((lang, quality));
+            }
+        }
+        best.map(|(lang, _)| lang)
+    }
+}
+
+/**
+ * All the text shown to the user in one language
+ *
+ * Templates use `{error}` for the error message and `{issue_tracker}` for a link to the issue
+ * tracker. The messages for invalid distances use `{max}` and `{step}` for the limits.
+ */
+pub struct Catalogue {
+    pub select_tournament: &'static str,
+    pub active_tournaments: &'static str,
+    pub no_active_tournaments: &'static str,
+    pub finished_tournaments: &'static str,
+    pub select_hole: &'static str,
+    pub no_holes: &'static str,
+    /// Link to the leaderboard with every hole in a tournament
+    pub all_holes: &'static str,
+    /// Followed by the hole number
+    pub hole: &'static str,
+    /// Followed by the name of the sponsor
+    pub sponsored_by: &'static str,
+    pub rank: &'static str,
+    pub name: &'static str,
+    pub score: &'static str,
+    pub no_scores: &'static str,
+    /// Read by screen readers after the names of scores submitted while not leading
+    pub not_leading: &'static str,
+    pub find_player: &'static str,
+    pub search: &'static str,
+    pub no_player_scores: &'static str,
+    pub submit_score: &'static str,
+    pub edit_score: &'static str,
+    /// Link to [`Catalogue::edit_score`], shown for a while after a score is submitted
+    pub change_score: &'static str,
+    /// The button that withdraws a score that is being changed
+    pub withdraw: &'static str,
+    /// Link to the scores as a CSV file
+    pub download_csv: &'static str,
+    /// Column heading in the CSV files
+    pub member_number: &'static str,
+    /// Column heading in the CSV files, followed by the unit
+    pub distance: &'static str,
+    /// Shown after the labels of fields that don't have to be filled in
+    pub optional: &'static str,
+    /// The button that submits the score
+    pub submit: &'static str,
+    pub invalid_name: &'static str,
+    pub invalid_member: &'static str,
+    pub invalid_score_m: &'static str,
+    pub invalid_score_cm: &'static str,
+
+    pub error_critical_server: &'static str,
+    pub error_invalid_query_string: &'static str,
+    pub error_network: &'static str,
+    pub error_backend_connection: &'static str,
+    pub error_backend_status: &'static str,
+    pub error_invalid_form: &'static str,
+    pub error_tournament_closed: &'static str,
+    pub error_invalid_token: &'static str,
+    pub error_too_many_requests: &'static str,
+    pub error_edit_expired: &'static str,
+    pub error_not_found: &'static str,
+}
+
+static DA: Catalogue = Catalogue {
+    select_tournament: "Vælg en turnering",
+    active_tournaments: "Aktive turneringer",
+    no_active_tournaments: "Ingen aktive turneringer",
+    finished_tournaments: "Afsluttede turneringer",
+    select_hole: "Vælg et hul",
+    no_holes: "Der er ingen huller i denne turnering",
+    all_holes: "Se førerne på alle huller",
+    hole: "Hul",
+    sponsored_by: "Sponsoreret af:",
+    rank: "Nr.",
+    name: "Navn",
+    score: "Score",
+    no_scores: "Der er ingen noteringer endnu",
+    not_leading: "(indsendt mens en anden førte)",
+    find_player: "Find en spiller",
+    search: "Søg",
+    no_player_scores: "Der er ingen noteringer fra denne spiller",
+    submit_score: "Indsend notering",
+    edit_score: "Ret notering",
+    change_score: "Ret eller træk din notering tilbage",
+    withdraw: "Træk tilbage",
+    download_csv: "Hent som regneark (CSV)",
+    member_number: "Medlemsnummer",
+    distance: "Afstand",
+    optional: "(ikke påkrævet)",
+    submit: "Indsend",
+    invalid_name: "Navnet skal være mellem 2 og 40 tegn langt",
+    invalid_member: "Medlemsnummeret skal være 2 til 11 tegn langt og kun bestå af cifre, eventuelt delt med '-' eller mellemrum",
+    invalid_score_m: "Meter skal være et helt tal fra 0 til {max}",
+    invalid_score_cm: "Centimeter skal være fra 0 til {max} i trin af {step}",
+
+    error_critical_server: "Der skete en fejl på serveren, dette er en bug. Rapporter fejlen her: {issue_tracker} <br> Inkluder følgende informationer i fejlrapporten: Sidens URL, denne fejlbesked: <pre>{error}</pre>",
+    error_invalid_query_string: "Der er en fejl i URL'en. Tjek at du har stavet den rigtigt og at du har fået det rigtige link. <br> URL'en burde ende med: <pre>....org/u?u=<u>brugernavn</u></pre>",
+    error_network: "Der skete en ukendt netværksfejl, dette er muligvis en bug. Fejlbesked: <pre>{error}</pre>",
+    error_backend_connection: "Bullseyegolf light kunne ikke kommunikere med API serveren, prøv igen senere.<br>Fejlbesked:<pre>{error}</pre>",
+    error_backend_status: "Der skete en fejl på API serveren. <pre>{error}</pre>",
+    error_invalid_form: "Dataen du har indsendt er ikke i det rigtige format, dette burde ikke ske. Luk siden og prøv igen.<br/> Fejlbesked:<pre>{error}</pre>",
+    error_tournament_closed: "Turneringen er afsluttet, så der kan ikke længere indsendes noteringer.",
+    error_invalid_token: "Din notering blev ikke indsendt, fordi formularen har været åben for længe eller ikke kommer fra Bullseyegolf light. <br> Gå tilbage til hullet, vælg 'Indsend notering' og prøv igen.",
+    error_too_many_requests: "Der er indsendt for mange noteringer til dette hul fra din forbindelse på kort tid. Vent lidt og prøv igen.",
+    error_edit_expired: "Din notering kan ikke længere rettes. Den kan kun rettes i 10 minutter efter den er indsendt, og kun fra den browser den blev indsendt fra.",
+    error_not_found: "Brugeren, turneringen eller hullet findes ikke. Tjek at du har fået det rigtige link.",
+};
+
+static EN: Catalogue = Catalogue {
+    select_tournament: "Choose a tournament",
+    active_tournaments: "Active tournaments",
+    no_active_tournaments: "No active tournaments",
+    finished_tournaments: "Finished tournaments",
+    select_hole: "Choose a hole",
+    no_holes: "There are no holes in this tournament",
+    all_holes: "See the leaders on every hole",
+    hole: "Hole",
+    sponsored_by: "Sponsored by:",
+    rank: "No.",
+    name: "Name",
+    score: "Score",
+    no_scores: "There are no scores yet",
+    not_leading: "(submitted while someone else was leading)",
+    find_player: "Find a player",
+    search: "Search",
+    no_player_scores: "There are no scores from this player",
+    submit_score: "Submit score",
+    edit_score: "Change score",
+    change_score: "Change or withdraw your score",
+    withdraw: "Withdraw",
+    download_csv: "Download as a spreadsheet (CSV)",
+    member_number: "Member number",
+    distance: "Distance",
+    optional: "(optional)",
+    submit: "Submit",
+    invalid_name: "The name has to be between 2 and 40 characters long",
+    invalid_member: "The member number has to be 2 to 11 characters long and only contain digits, optionally split by '-' or a space",
+    invalid_score_m: "Meters has to be a whole number from 0 to {max}",
+    invalid_score_cm: "Centimeters has to be from 0 to {max} in steps of {step}",
+
+    error_critical_server: "An error happened on the server, this is a bug. Report it here: {issue_tracker} <br> Include the following information in the report: The URL of the page, this error message: <pre>{error}</pre>",
+    error_invalid_query_string: "There is an error in the URL. Check that it is spelled correctly and that you got the right link. <br> The URL should end with: <pre>....org/u?u=<u>username</u></pre>",
+    error_network: "An unknown network error happened, this might be a bug. Error message: <pre>{error}</pre>",
+    error_backend_connection: "Bullseyegolf light could not communicate with the API server, try again later.<br>Error message:<pre>{error}</pre>",
+    error_backend_status: "An error happened on the API server. <pre>{error}</pre>",
+    error_invalid_form: "The data you submitted is not in the right format, this shouldn't happen. Close the page and try again.<br/> Error message:<pre>{error}</pre>",
+    error_tournament_closed: "The tournament has finished, so scores can't be submitted anymore.",
+    error_invalid_token: "Your score wasn't submitted, because the form has been open for too long or doesn't come from Bullseyegolf light. <br> Go back to the hole, choose 'Submit score' and try again.",
+    error_too_many_requests: "Too many scores have been submitted to this hole from your connection in a short time. Wait a little and try again.",
+    error_edit_expired: "Your score can't be changed anymore. It can only be changed for 10 minutes after it was submitted, and only from the browser it was submitted from.",
+    error_not_found: "The user, tournament or hole doesn't exist. Check that you got the right link.",
+};
@@ -1,26 +1,251 @@
 #![recursion_limit = "512"]
+/// Where the tournaments and scores come from, the API server or a JSON file
+mod backend;
+/// Settings for the deployment and for single tournaments
+mod config;
+/// Protects the score submission from forms on other sites
+mod csrf;
+/// CSV files for copying scores into spreadsheets
+mod csv;
+/// Lets players change or withdraw a score they just submitted
+mod edit;
 /// Error handling for the entire program
 mod error;
+/// FastCGI transport, used when started with `--fastcgi` or by a FastCGI web server
+mod fastcgi;
+/// Formatting of numbers and distances for the negotiated locale
+mod format;
 /// Generates the requested page
 mod get;
+/// The text of the user interface in every supported language
+mod i18n;
+/// Which scores were submitted while another player was leading
+mod not_leading;
 /// Forwards the score submission to the API server and redirects to [`get::ViewHolePage`]
 mod post;
+/// Limits how many scores a client can submit
+mod rate_limit;
+/// The HTTP request as seen by the rest of the program, independent of the transport
+mod request;
+/// The HTTP response produced by the rest of the program, independent of the transport
+mod response;
+/// Standalone HTTP/1.1 server, used instead of CGI when started with `--listen`
+mod server;
+/// Where state that is kept between requests is stored
+mod state;
+use config::Config;
+use error::Error;
+use format::Unit;
+use get::Format;
+use i18n::Lang;
+use request::Request;
+use response::Response;
 use std::env;
+use std::io::{stdin, stdout, Write};
+use std::path::PathBuf;
+use std::process::exit;
 
 /**
  * Main entrypoint for the program
  *
- * Calls the correct module depending on the HTTP method used.
- * Also handles errors by printing them to stdout
+ * Runs as a CGI script unless
+ * - `--listen <addr>` is given, in which case it serves HTTP itself.
+ *   `--document-root <dir>` sets where the static files are served from in that mode.
+ * - `--fastcgi [<addr>]` is given or stdin is a socket, in which case it stays resident and
+ *   serves FastCGI on `addr`, or on the socket passed as stdin.
  */
 fn main() {
-    let response = if env::var("REQUEST_METHOD") == Ok("POST".to_owned()) {
-        post::post()
-    } else {
-        get::get()
+    // Some web servers pass parts of the query string as arguments to CGI scripts
+    if env::var_os("GATEWAY_INTERFACE").is_some() {
+        return cgi();
+    }
+
+    let mut listen = None;
+    let mut fastcgi = None;
+    let mut document_root = PathBuf::from("../server/document-root");
+
+    let mut args = env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => listen = Some(args.next().unwrap_or_else(|| usage())),
+            "--document-root" => document_root = args.next().unwrap_or_else(|| usage()).into(),
+            "--fastcgi" => fastcgi = Some(args.next_if(|a| !a.starts_with("--"))),
+            _ => usage(),
+        }
+    }
+
+    if listen.is_none() && fastcgi.is_none() && !fastcgi::stdin_is_listener() {
+        return cgi();
+    }
+
+    // The resident modes only read the environment once, and keep the cache in memory
+    let Ok(server) = env::var("SERVER_URL") else {
+        eprintln!("SERVER_URL must be set to the URL of the API server");
+        exit(2);
     };
-    match response {
-        Ok(t) => println!("{t}"),
-        Err(e) => println!("{e}"),
+    let server: &'static str = server.leak();
+    let config: &'static Config = match Config::from_env() {
+        Ok(mut config) => {
+            config.state = config.state.with_memory_cache();
+            Box::leak(Box::new(config))
+        }
+        Err(Error::CriticalServer(e)) => {
+            eprintln!("Invalid configuration: {e}");
+            exit(2);
+        }
+        Err(e) => {
+            eprintln!(
+                "Invalid configuration: {}",
+                e.plain_message(Lang::default())
+            );
+            exit(2);
+        }
+    };
+
+    let result = match (listen, fastcgi) {
+        (Some(_), Some(_)) => usage(),
+        (Some(addr), None) => server::listen(&addr, document_root, server, config),
+        (None, Some(Some(addr))) => fastcgi::listen(&addr, server, config),
+        (None, _) => fastcgi::serve_stdin(server, config),
+    };
+    if let Err(e) = result {
+        eprintln!("Could not serve requests: {e}");
+        exit(1);
+    }
+}
+
+/// Runs the program as a CGI script
+fn cgi() {
+    let response = env::var("SERVER_URL")
+        .map_err(Error::from)
+        .and_then(|server| {
+            let request = Request::from_cgi(env::vars(), stdin())
+                .map_err(|e| Error::CriticalServer(Box::new(e)))?;
+            Ok(match Config::from_env() {
+                Ok(config) => respond(&request, &server, &config),
+                Err(e) => e.into_response(Lang::negotiate(&request), Format::negotiate(&request)),
+            })
+        })
+        .unwrap_or_else(Response::from);
+
+    let mut stdout = stdout().lock();
+    if let Err(e) = response.write_cgi(&mut stdout).and_then(|_| stdout.flush()) {
+        eprintln!("Could not write the response: {e}");
+    }
+}
+
+/**
+ * Handles a single request, no matter which transport it came from
+ *
+ * Calls the correct module depending on the HTTP method used.
+ * Also handles errors by rendering them as the response.
+ * `server` is the URL of the API server and `config` the settings of the deployment.
+ */
+fn respond(request: &Request, server: &str, config: &Config) -> Response {
+    let lang = Lang::negotiate(request);
+    let response = match request.method.as_str() {
+        "POST" => post::post(request, server, config, lang),
+        _ => get::get(request, server, config, lang),
+    };
+    let response = response.unwrap_or_else(|e| e.into_response(lang, Format::negotiate(request)));
+
+    // Remember explicitly chosen settings for the following pages
+    let lang = Lang::from_query(&request.query).map(|l| ("lang", l.code()));
+    let unit = Unit::from_query(&request.query).map(|u| ("units", u.code()));
+    lang.into_iter()
+        .chain(unit)
+        .fold(response, |response, (name, value)| {
+            response.with_header(
+                "Set-Cookie",
+                format!("{name}={value}; Path=/; Max-Age=31536000; SameSite=Lax"),
+            )
+        })
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "Usage: bullseyegolf-frontend-light-user [--listen <addr>] [--document-root <dir>] [--fastcgi [<addr>]]"
+    );
+    exit(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use get::ViewHolePage;
+    use std::fs;
+
+    /// The id of the `csrf` cookie every request is sent with
+    const CLIENT: &str = "0123456789abcdef0123456789abcdef";
+
+    /**
+     * The settings for a test, with the state in a temporary directory and the scores in a copy
+     * of the demo file named after `test`, so they can be submitted to
+     */
+    pub fn setup(test: &str) -> (String, Config) {
+        let dir = env::temp_dir()
+            .join(format!("bullseyegolf-light-{}", std::process::id()))
+            .join(test);
+        let demo = dir.join("demo.json");
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/../server/demo.json"),
+            &demo,
+        )
+        .unwrap();
+        let server = format!("file://{}", demo.display());
+        let config = Config::from_env()
+            .unwrap()
+            .with_state_dir(dir.join("state"));
+        (server, config)
+    }
+
+    /// Responds to a request from 127.0.0.1 with a `csrf` cookie, as it would be sent over HTTP
+    fn send(method: &str, query: &str, body: &str, server: &str, config: &Config) -> String {
+        let request = Request {
+            method: method.to_owned(),
+            path: "/u".to_owned(),
+            query: query.to_owned(),
+            remote_addr: "127.0.0.1".to_owned(),
+            headers: vec![("cookie".to_owned(), format!("csrf={CLIENT}"))],
+            body: body.as_bytes().to_vec(),
+        };
+        let mut http = Vec::new();
+        respond(&request, server, config)
+            .write_http(&mut http)
+            .unwrap();
+        String::from_utf8(http).unwrap()
+    }
+
+    #[test]
+    fn get_pages() {
+        let (server, config) = setup("get_pages");
+        let page = send("GET", "u=demo&t=sommer&h=3", "", &server, &config);
+        assert!(page.starts_with("HTTP/1.1 200"), "{page}");
+        let page = send("GET", "u=demo&t=winter", "", &server, &config);
+        assert!(page.starts_with("HTTP/1.1 404"), "{page}");
+    }
+
+    #[test]
+    fn post_scores() {
+        let (server, config) = setup("post_scores");
+        let hole = ViewHolePage {
+            user: "demo".to_owned(),
+            tournament: "sommer".to_owned(),
+            hole: 3,
+        };
+        let token = csrf::token(&hole, CLIENT, &config.secret).unwrap();
+
+        let body = format!("token={token}&name=Dora&score_m=3&score_cm=5");
+        let response = send("POST", "u=demo&t=sommer&h=3", &body, &server, &config);
+        assert!(response.starts_with("HTTP/1.1 303"), "{response}");
+        let scores = send(
+            "GET",
+            "u=demo&t=sommer&h=3&format=json",
+            "",
+            &server,
+            &config,
+        );
+        assert!(scores.contains("\"Dora\""), "{scores}");
     }
 }
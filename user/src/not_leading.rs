@@ -0,0 +1,82 @@
+use crate::get::{Score, ViewHolePage};
+use crate::state::Store;
+
+/// How long it's remembered which scores were submitted while not leading, in seconds
+const TTL: u64 = 365 * 24 * 60 * 60;
+
+/// What used to be appended to the names of scores that were submitted while not leading
+const LEGACY_SUFFIX: &str = " 🏴";
+
+/**
+ * Remembers whether `score` on `hole` was submitted while another score was better
+ *
+ * The API server only stores names and distances, so it's kept in the state store with a list
+ * of the scores per hole. That makes the flag local to the instances sharing the `STATE_DIR`:
+ * scores submitted to the API server some other way are never flagged, and other programs using
+ * the API server don't see it. The flag is only shown on the pages, so if the store can't be
+ * written that's logged and the score is submitted without it
+ */
+pub fn record(state: &Store, hole: &ViewHolePage, score: &Score, not_leading: bool) {
+    let recorded = state.update(&namespace(hole), SCORES, TTL, |value| {
+        let mut scores = parse(value.as_deref());
+        scores.retain(|s| s != score);
+        if not_leading {
+            scores.push(score.clone());
+        }
+        *value = match scores.is_empty() {
+            true => None,
+            false => serde_json::to_string(&scores).ok(),
+        };
+    });
+    if let Err(e) = recorded {
+        eprintln!("Could not record whether a score was leading: {e:?}");
+    }
+}
+
+/**
+ * Sets [`Score::not_leading`] on the `scores` that were recorded on `hole` in `state`
+ *
+ * Scores submitted before it was recorded have the "🏴" suffix on the name instead, which is
+ * removed. Pages are shown far more often than scores are submitted, so this only takes a shared
+ * lock. If the state store can't be read none of the other scores are marked, rather than not
+ * showing the page at all
+ */
+pub fn mark(state: &Store, hole: &ViewHolePage, scores: &mut [Score]) {
+    let recorded = match state.get(&namespace(hole), SCORES) {
+        Ok(value) => parse(value.as_deref()),
+        Err(e) => {
+            eprintln!("Could not read which scores were not leading: {e:?}");
+            Vec::new()
+        }
+    };
+    for score in scores {
+        if let Some(name) = score.player_name.strip_suffix(LEGACY_SUFFIX) {
+            score.player_name.truncate(name.len());
+            score.not_leading = true;
+        }
+        score.not_leading |= recorded.contains(score);
+    }
+}
+
+/// The key of the list of scores in the [`namespace`] of a hole
+const SCORES: &str = "scores";
+
+/**
+ * Every hole has a namespace of its own, so showing a hole doesn't read the flags of every other
+ * hole that had scores submitted in the last year
+ *
+ * The user and tournament are valid ids (see [`ViewHolePage::is_valid`]), so they can be used as
+ * directory names.
+ */
+fn namespace(hole: &ViewHolePage) -> String {
+    format!(
+        "not_leading/{}/{}/{}",
+        hole.user, hole.tournament, hole.hole
+    )
+}
+
+fn parse(value: Option<&str>) -> Vec<Score> {
+    value
+        .and_then(|v| serde_json::from_str(v).ok())
+        .unwrap_or_default()
+}
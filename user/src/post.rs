@@ -1,26 +1,160 @@
+use crate::backend::{self, Backend};
+use crate::config::{Config, Duplicates, Limits};
+use crate::csrf;
+use crate::edit::Edit;
 use crate::error::Error;
-use crate::get::{Fetch, Hole, Score, ViewHolePage};
+use crate::format::Unit;
+use crate::get::{
+    split_member_number, Context, Format, Hole, Score, SubmitScorePage, ViewHolePage,
+};
+use crate::i18n::Lang;
+use crate::not_leading;
+use crate::rate_limit;
+use crate::request::Request;
+use crate::response::Response;
+use crate::state::Store;
 use serde::Deserialize;
 use serde_urlencoded as qs;
-use std::env;
-use std::io::stdin;
-
-/// Main entrypoint for score submission
-pub fn post() -> Result<String, Error> {
-    let params = Params::new()?;
-    let score: Score = qs::from_reader::<CustomScore, _>(stdin())
-        .map_err(Error::InvalidForm)?
-        .into();
-
-    let leaderboard = Hole::fetch(&params.server, &params.query_args)?.scores;
-    if !score.is_duplicate(&leaderboard) {
-        submit_score(&params, score, &leaderboard)?;
-    }
 
-    Ok(format!(
-        "Status: 303\r\nLocation: ?u={}&t={}&h={}\r\n\r\n\r\n",
+/**
+ * Main entrypoint for score submission
+ *
+ * If the score isn't valid the form is shown again with the player's input and what's wrong.
+ * A new score can be changed or withdrawn by the player for a while if the backend allows it,
+ * see [`Edit`].
+ */
+pub fn post(
+    request: &Request,
+    server: &str,
+    config: &Config,
+    lang: Lang,
+) -> Result<Response, Error> {
+    let params = Params::new(request, server, config)?;
+    let form = qs::from_bytes::<CustomScore>(&request.body).map_err(Error::InvalidForm)?;
+    csrf::verify(&form.token, &params.query_args, request, &config.secret)?;
+    let edit = match form.edit.is_empty() {
+        true => None,
+        false => Edit::from_request(request, config)?
+            .filter(|e| e.id == form.edit && e.is_for(&params.query_args))
+            .filter(|_| params.backend.can_delete())
+            .map(Some)
+            .ok_or(Error::EditExpired)?,
+    };
+    let location = format!(
+        "?u={}&t={}&h={}",
         params.query_args.user, params.query_args.tournament, params.query_args.hole
-    ))
+    );
+
+    if let (Some(edit), false) = (&edit, form.withdraw.is_empty()) {
+        delete_score(&params, &edit.score)?;
+        let cookie = edit.clone().revoke(config)?;
+        return Ok(Response::redirect(location).with_header("Set-Cookie", cookie));
+    }
+
+    let limits = config.limits(Some(&params.query_args.tournament));
+    let score = match form.validate(limits) {
+        Ok(score) => score,
+        Err(invalid) => {
+            let editing = edit.is_some();
+            let ctx = Context {
+                backend: params.backend,
+                lang,
+                unit: Unit::negotiate(request),
+                format: Format::Html,
+                limits,
+                edit,
+                member_numbers: config.member_numbers,
+                state: config.state.clone(),
+                secret: config.secret.clone(),
+            };
+            let page = SubmitScorePage {
+                user: params.query_args.user,
+                tournament: params.query_args.tournament,
+                hole: params.query_args.hole,
+                client: csrf::Client::from_request(request)?.id,
+                input: form,
+                invalid,
+                editing,
+            };
+            return page.respond_invalid(&ctx);
+        }
+    };
+
+    let mut leaderboard =
+        Hole::fetch(params.backend.as_ref(), &config.state, &params.query_args)?.scores;
+    if let Some(mut edit) = edit {
+        delete_score(&params, &edit.score)?;
+        leaderboard.retain(|s| *s != edit.score);
+        edit.score = match submit_score(&params, score, &leaderboard) {
+            Ok(score) => score,
+            Err(e) => {
+                // The API server can't replace a score in one request, so the old one is put back
+                if let Err(restore) = submit_score(&params, edit.score.clone(), &leaderboard) {
+                    eprintln!("Could not put back a score after changing it failed: {restore:?}");
+                }
+                return Err(e);
+            }
+        };
+        edit.save(config)?;
+        return Ok(Response::redirect(location));
+    }
+
+    let duplicates = config.duplicates;
+    let key = is_key(&form.key).then_some(form.key.as_str());
+    let compare = match (duplicates, key) {
+        (Duplicates::Off, _) | (Duplicates::Key, Some(_)) => false,
+        (Duplicates::Key, None) | (Duplicates::Content, _) => true,
+    };
+    if compare && score.is_duplicate(&leaderboard, limits) {
+        return Ok(Response::redirect(location));
+    }
+    let key = key.filter(|_| duplicates != Duplicates::Off);
+    if let Some(key) = key {
+        if !first_use(&config.state, key, true)? {
+            return Ok(Response::redirect(location));
+        }
+    }
+
+    let client = &request.remote_addr;
+    let submitted = rate_limit::check(config.rate_limit, &config.state, client, &params.query_args)
+        .and_then(|_| submit_score(&params, score, &leaderboard));
+    let score = match submitted {
+        Ok(score) => score,
+        Err(e) => {
+            // So the player can try again with the same form, also after being rate limited
+            if let Some(key) = key {
+                first_use(&config.state, key, false)?;
+            }
+            return Err(e);
+        }
+    };
+    if !params.backend.can_delete() {
+        return Ok(Response::redirect(location));
+    }
+    let edit = Edit::grant(&params.query_args, score, config)?;
+    Ok(Response::redirect(location).with_header("Set-Cookie", edit.cookie(config)?))
+}
+
+/// How long the key of a submitted form is remembered, longer than the form is valid for
+const KEY_TTL: u64 = 3 * 60 * 60;
+
+/**
+ * Marks the form `key` as used, or unused if `used` is false
+ *
+ * Returns whether it wasn't used already. Concurrent submissions of the same form see each
+ * other, since [`Store::update`] is atomic.
+ */
+fn first_use(state: &Store, key: &str, used: bool) -> Result<bool, Error> {
+    state.update("submissions", key, KEY_TTL, |value| {
+        let first = value.is_none();
+        *value = used.then(String::new);
+        first
+    })
+}
+
+/// Whether `key` looks like the one [`crate::get::SubmitScorePage`] puts in the form
+pub fn is_key(key: &str) -> bool {
+    key.len() == 32 && key.bytes().all(|b| b.is_ascii_hexdigit())
 }
 
 /**
@@ -29,81 +163,248 @@ pub fn post() -> Result<String, Error> {
  * This is different from [`crate::get`]'s `Params` because none of the `query_args` are optional
  */
 struct Params {
-    server: String,
+    backend: Box<dyn Backend>,
     query_args: ViewHolePage,
+    /// Where [`crate::not_leading`] keeps its flags
+    state: Store,
 }
 
 impl Params {
-    pub fn new() -> Result<Self, Error> {
-        let server = env::var("SERVER_URL")?;
-        let query_string = env::var("HTTP_REFERER").map_err(|_| Error::Referer)?;
-        let query_string = query_string.split_once('?').ok_or(Error::Referer)?.1;
-        let query_args = qs::from_str(query_string)?;
-        Ok(Params { server, query_args })
+    /**
+     * The hole is only taken from the query string, which the form puts in its action
+     *
+     * There deliberately is no fallback to the `Referer` header for forms from before that. Those
+     * forms have no token, so they can only be accepted by skipping [`csrf::verify`], and then
+     * any other site could submit scores again. Players with old forms get an error and load the
+     * form again.
+     */
+    pub fn new(request: &Request, server: &str, config: &Config) -> Result<Self, Error> {
+        let query_args: ViewHolePage = qs::from_str(&request.query)?;
+        if !query_args.is_valid() {
+            return Err(Error::InvalidQueryString);
+        }
+        let backend = backend::open(server, config)?;
+        Ok(Params {
+            backend,
+            query_args,
+            state: config.state.clone(),
+        })
     }
 }
 
 /**
  * The custom format for a score used by the HTML form
  *
- * Can be losslessly converted to a proper [`Score`]
+ * The fields are kept as the player typed them, so the form can be shown again with them if
+ * [`CustomScore::validate`] fails
  */
-#[derive(Deserialize, Debug)]
-struct CustomScore {
-    name: String,
-    member: Option<String>,
-    score_m: u8,
-    score_cm: u8,
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct CustomScore {
+    pub name: String,
+    pub member: String,
+    pub score_m: String,
+    pub score_cm: String,
+    /// See [`crate::csrf`]
+    pub token: String,
+    /// Random for every form, so submitting it twice can be noticed, see [`Duplicates`]
+    pub key: String,
+    /// The id of the [`Edit`] when changing a score
+    pub edit: String,
+    /// Set by the button that withdraws the score being changed
+    pub withdraw: String,
+}
+
+/// The fields of [`CustomScore`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Name,
+    Member,
+    ScoreM,
+    ScoreCm,
 }
 
-impl From<CustomScore> for Score {
-    fn from(value: CustomScore) -> Self {
-        let player_name = format!(
-            "{}{}",
-            value.member.map(|m| m + " ").unwrap_or_default(),
-            value.name
-        );
+impl Field {
+    /// What has to be fixed, shown next to the field
+    pub fn message(self, lang: Lang, limits: Limits) -> String {
+        let text = lang.text();
+        let number = |n: f64| {
+            n.to_string()
+                .replace('.', &lang.decimal_separator().to_string())
+        };
+        match self {
+            Self::Name => text.invalid_name.to_owned(),
+            Self::Member => text.invalid_member.to_owned(),
+            Self::ScoreM => text
+                .invalid_score_m
+                .replace("{max}", &limits.max_meters.to_string()),
+            Self::ScoreCm => text
+                .invalid_score_cm
+                .replace("{max}", &number(limits.max_cm()))
+                .replace("{step}", &number(limits.resolution_cm)),
+        }
+    }
+}
+
+impl CustomScore {
+    /// The form filled in with `score`, for changing it
+    pub fn from_score(score: &Score, limits: Limits) -> Self {
+        let steps = (score.player_score * 100.0 / limits.resolution_cm).round();
+        let total_cm = steps * limits.resolution_cm;
+        let m = (total_cm / 100.0).floor();
+        // Rounded again so floating point noise doesn't end up in the field
+        let cm = ((total_cm - m * 100.0) * 1e6).round() / 1e6;
         Self {
-            player_name,
-            player_score: value.score_m as f64 + value.score_cm as f64 * 0.01,
+            name: score.bare_name().to_owned(),
+            member: score.member_number().unwrap_or_default().to_owned(),
+            score_m: m.to_string(),
+            score_cm: cm.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /**
+     * Converts the form to a proper [`Score`], or lists the fields that aren't valid
+     *
+     * These are the same rules as the attributes on the form enforce in the browser
+     */
+    pub fn validate(&self, limits: Limits) -> Result<Score, Vec<Field>> {
+        let name = self.name.trim();
+        let member = self.member.trim();
+        let score_m: Option<u8> = self
+            .score_m
+            .trim()
+            .parse()
+            .ok()
+            .filter(|m| *m <= limits.max_meters);
+        let score_cm: Option<f64> = self
+            .score_cm
+            .trim()
+            .replace(',', ".")
+            .parse()
+            .ok()
+            .filter(|cm| limits.is_valid_cm(*cm));
+
+        let mut invalid = Vec::new();
+        if !(2..=40).contains(&name.chars().count()) {
+            invalid.push(Field::Name);
+        }
+        // A separator at either end would not be split off the name again, see `Score::bare_name`
+        let member_number = member.trim_matches(['-', ' ']);
+        let valid_member = is_member_number(member) && is_member_number(member_number);
+        if !member.is_empty() && !valid_member {
+            invalid.push(Field::Member);
+        }
+        if score_m.is_none() {
+            invalid.push(Field::ScoreM);
+        }
+        if score_cm.is_none() {
+            invalid.push(Field::ScoreCm);
         }
+
+        match (score_m, score_cm) {
+            (Some(m), Some(cm)) if invalid.is_empty() => Ok(Score {
+                player_name: player_name(member_number, name),
+                player_score: m as f64 + cm * 0.01,
+                not_leading: false,
+            }),
+            _ => Err(invalid),
+        }
+    }
+}
+
+/**
+ * `name` with `member` in front of it, which is how the API server gets the member number
+ *
+ * If that would be split differently by [`Score::member_number`], like "10 Pins" without a
+ * member number, the spaces in the digits at the start of `name` become no-break spaces. They
+ * look the same, but aren't taken for the space after a member number.
+ */
+fn player_name(member: &str, name: &str) -> String {
+    let join = |name: &str| match member {
+        "" => name.to_owned(),
+        member => format!("{member} {name}"),
+    };
+    let player_name = join(name);
+    if split_member_number(&player_name).0 == Some(member).filter(|m| !m.is_empty()) {
+        return player_name;
     }
+    let digits = name
+        .find(|c: char| !c.is_ascii_digit() && c != '-' && c != ' ')
+        .unwrap_or(name.len());
+    join(&(name[..digits].replace(' ', "\u{a0}") + &name[digits..]))
+}
+
+/// Whether `member` matches `\d*[\- ]?\d*` and is 2 to 11 characters long
+pub fn is_member_number(member: &str) -> bool {
+    fn skip_digits(s: &str) -> &str {
+        s.trim_start_matches(|c: char| c.is_ascii_digit())
+    }
+
+    let rest = skip_digits(member);
+    let rest = rest.strip_prefix(['-', ' ']).unwrap_or(rest);
+    (2..=11).contains(&member.len()) && skip_digits(rest).is_empty()
 }
 
 impl Score {
     fn is_first(&self, leaderboard: &[Score]) -> bool {
         leaderboard
-            .get(0)
+            .first()
             .map(|s| self.player_score < s.player_score)
             .unwrap_or(true)
     }
 
-    /// Does this Score already exist in the given leaderboard
-    fn is_duplicate(&self, leaderboard: &[Score]) -> bool {
-        leaderboard.iter().any(|s| s == self)
+    /**
+     * Whether a score from the same player at the same distance is in `leaderboard`
+     *
+     * Distances are the same if they round to the same step of `limits`. If both scores have a
+     * member number the player is identified by that, since the name can be typed differently.
+     * Otherwise the names are compared with [`normalize`]
+     */
+    fn is_duplicate(&self, leaderboard: &[Score], limits: Limits) -> bool {
+        let tolerance = limits.resolution_cm / 200.0;
+        let same_player = |other: &Score| match (self.member_number(), other.member_number()) {
+            (Some(a), Some(b)) => normalize(a) == normalize(b),
+            (None, None) => normalize(self.bare_name()) == normalize(other.bare_name()),
+            _ => false,
+        };
+        leaderboard
+            .iter()
+            .any(|s| (s.player_score - self.player_score).abs() < tolerance && same_player(s))
     }
 }
 
+/// `name` in lowercase with the whitespace trimmed and collapsed, for comparing names
+pub fn normalize(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
 /**
- * Submits `score` to the API server
- *
- * Only submits if `score` is not already present in `leaderboard`
+ * Submits `score` to the [`Backend`], returns it as it was submitted
  *
- * If `score` is not first in `leaderboard` the "🏴" emoji is appended to its name
+ * If `score` is not first in `leaderboard` that is recorded, see [`crate::not_leading`]
  */
-fn submit_score(
-    params: &Params,
-    mut score: Score,
-    leaderboard: &[Score],
-) -> Result<reqwest::blocking::Response, Error> {
-    if !score.is_first(leaderboard) {
-        score.player_name += " 🏴";
-    }
-
-    let url = format!(
-        "{}/{}/{}/{}",
-        params.server, params.query_args.user, params.query_args.tournament, params.query_args.hole
+fn submit_score(params: &Params, score: Score, leaderboard: &[Score]) -> Result<Score, Error> {
+    params.backend.submit(&params.query_args, &score)?;
+    not_leading::record(
+        &params.state,
+        &params.query_args,
+        &score,
+        !score.is_first(leaderboard),
     );
-    let client = reqwest::blocking::Client::new();
-    Ok(client.post(url).json(&score).send()?.error_for_status()?)
+    Ok(score)
+}
+
+/**
+ * Removes `score` from the hole in the [`Backend`]
+ *
+ * The API server identifies the score by its content, so it's sent exactly as it was submitted
+ */
+fn delete_score(params: &Params, score: &Score) -> Result<(), Error> {
+    params.backend.delete(&params.query_args, score)?;
+    not_leading::record(&params.state, &params.query_args, score, false);
+    Ok(())
 }
@@ -0,0 +1,45 @@
+use crate::config::RateLimit;
+use crate::error::Error;
+use crate::get::ViewHolePage;
+use crate::state::{now, Store};
+
+/**
+ * Counts a score submitted by `client` to `hole`
+ *
+ * Fails with [`Error::TooManyRequests`] instead if `client` has already submitted as many scores
+ * to `hole` as `limit` allows. `client` is the address the request came from.
+ */
+pub fn check(
+    limit: RateLimit,
+    state: &Store,
+    client: &str,
+    hole: &ViewHolePage,
+) -> Result<(), Error> {
+    if limit.submissions == 0 {
+        return Ok(());
+    }
+
+    let key = format!("{client} {}/{}/{}", hole.user, hole.tournament, hole.hole);
+    let now = now()?;
+    // The value is the times of the recent submissions
+    let retry_after = state.update("rate_limit", &key, limit.seconds, |value| {
+        let mut times: Vec<u64> = value
+            .iter()
+            .flat_map(|v| v.split(','))
+            .filter_map(|t| t.parse().ok())
+            .filter(|t| t + limit.seconds > now)
+            .collect();
+        if times.len() >= limit.submissions {
+            return Some(times[0] + limit.seconds - now);
+        }
+        times.push(now);
+        let times: Vec<String> = times.iter().map(u64::to_string).collect();
+        *value = Some(times.join(","));
+        None
+    })?;
+
+    match retry_after {
+        Some(seconds) => Err(Error::TooManyRequests(seconds)),
+        None => Ok(()),
+    }
+}
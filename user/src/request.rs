@@ -0,0 +1,142 @@
+use std::io::{self, BufRead, Read};
+
+/// The largest request body that will be accepted, score submissions are tiny
+pub const MAX_BODY: u64 = 16 * 1024;
+/// The longest request line or header line [`Request::from_http`] accepts
+const MAX_LINE: u64 = 8 * 1024;
+/// The most headers [`Request::from_http`] accepts
+const MAX_HEADERS: usize = 100;
+
+/**
+ * An HTTP request, independent of the transport it arrived with
+ *
+ * Header names are stored in lowercase
+ */
+#[derive(Debug, Default)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    /// The address of the client, if the transport knows it
+    pub remote_addr: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /**
+     * Builds a request from CGI meta-variables, as used by both CGI and FastCGI
+     *
+     * `HTTP_*` variables become headers, `body` is only read up to `CONTENT_LENGTH`,
+     * since some web servers never close stdin
+     */
+    pub fn from_cgi(
+        vars: impl IntoIterator<Item = (String, String)>,
+        body: impl Read,
+    ) -> io::Result<Self> {
+        let mut request = Request::default();
+        let mut content_length = None;
+
+        for (name, value) in vars {
+            match name.as_str() {
+                "REQUEST_METHOD" => request.method = value,
+                "SCRIPT_NAME" => request.path.insert_str(0, &value),
+                "PATH_INFO" => request.path.push_str(&value),
+                "QUERY_STRING" => request.query = value,
+                "REMOTE_ADDR" => request.remote_addr = value,
+                "CONTENT_LENGTH" => content_length = value.parse().ok(),
+                "CONTENT_TYPE" => request.headers.push(("content-type".to_owned(), value)),
+                _ => {
+                    if let Some(header) = name.strip_prefix("HTTP_") {
+                        let header = header.to_ascii_lowercase().replace('_', "-");
+                        request.headers.push((header, value));
+                    }
+                }
+            }
+        }
+
+        let limit = content_length.unwrap_or(0).min(MAX_BODY);
+        body.take(limit).read_to_end(&mut request.body)?;
+        Ok(request)
+    }
+
+    /**
+     * Reads an HTTP/1.1 request, without support for chunked bodies
+     *
+     * Lines longer than [`MAX_LINE`] and more than [`MAX_HEADERS`] headers are rejected as
+     * malformed, so a client can't make the server use more and more memory. Bodies longer than
+     * [`MAX_BODY`] fail with [`io::ErrorKind::FileTooLarge`].
+     */
+    pub fn from_http(reader: &mut impl BufRead) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed HTTP request");
+
+        let mut line = String::new();
+        read_line(reader, &mut line)?;
+        let mut request_line = line.split_whitespace();
+        let method = request_line.next().ok_or_else(invalid)?.to_owned();
+        let target = request_line.next().ok_or_else(invalid)?;
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let mut request = Request {
+            method,
+            path: path.to_owned(),
+            query: query.to_owned(),
+            ..Default::default()
+        };
+
+        loop {
+            line.clear();
+            if read_line(reader, &mut line)? == 0 {
+                return Err(invalid());
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if request.headers.len() == MAX_HEADERS {
+                return Err(invalid());
+            }
+            let (name, value) = header.split_once(':').ok_or_else(invalid)?;
+            request
+                .headers
+                .push((name.to_ascii_lowercase(), value.trim().to_owned()));
+        }
+
+        let content_length = match request.header("content-length") {
+            Some(l) => l.parse().map_err(|_| invalid())?,
+            None => 0,
+        };
+        if content_length > MAX_BODY {
+            let error = "request body too large";
+            return Err(io::Error::new(io::ErrorKind::FileTooLarge, error));
+        }
+        reader.take(content_length).read_to_end(&mut request.body)?;
+        Ok(request)
+    }
+
+    /// The value of the first header called `name`, which has to be lowercase
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The value of the cookie called `name`
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.header("cookie")?
+            .split(';')
+            .filter_map(|c| c.trim().split_once('='))
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v)
+    }
+}
+
+/// [`BufRead::read_line`], but lines longer than [`MAX_LINE`] are malformed
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<usize> {
+    let read = reader.take(MAX_LINE).read_line(line)?;
+    if read as u64 == MAX_LINE && !line.ends_with('\n') {
+        let error = "HTTP request line too long";
+        return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+    }
+    Ok(read)
+}
@@ -0,0 +1,229 @@
+use std::io::{self, Write};
+
+/**
+ * An HTTP response, independent of the transport it will be sent with
+ *
+ * Can only be created with [`Response::builder`], which makes sure that the headers every
+ * response needs are always present
+ */
+#[derive(Debug)]
+pub struct Response {
+    status: Status,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+/// The HTTP status codes the frontend uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    SeeOther,
+    BadRequest,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    ContentTooLarge,
+    UnprocessableContent,
+    TooManyRequests,
+    InternalServerError,
+    BadGateway,
+    ServiceUnavailable,
+}
+
+impl Status {
+    pub fn code(self) -> u16 {
+        use Status::*;
+        match self {
+            Ok => 200,
+            SeeOther => 303,
+            BadRequest => 400,
+            Forbidden => 403,
+            NotFound => 404,
+            MethodNotAllowed => 405,
+            ContentTooLarge => 413,
+            UnprocessableContent => 422,
+            TooManyRequests => 429,
+            InternalServerError => 500,
+            BadGateway => 502,
+            ServiceUnavailable => 503,
+        }
+    }
+
+    pub fn reason(self) -> &'static str {
+        use Status::*;
+        match self {
+            Ok => "OK",
+            SeeOther => "See Other",
+            BadRequest => "Bad Request",
+            Forbidden => "Forbidden",
+            NotFound => "Not Found",
+            MethodNotAllowed => "Method Not Allowed",
+            ContentTooLarge => "Content Too Large",
+            UnprocessableContent => "Unprocessable Content",
+            TooManyRequests => "Too Many Requests",
+            InternalServerError => "Internal Server Error",
+            BadGateway => "Bad Gateway",
+            ServiceUnavailable => "Service Unavailable",
+        }
+    }
+}
+
+/// The media types the frontend responds with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    Html,
+    Css,
+    Plain,
+    Json,
+    Csv,
+}
+
+impl ContentType {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Html => "text/html; charset=utf-8",
+            Self::Css => "text/css; charset=utf-8",
+            Self::Plain => "text/plain; charset=utf-8",
+            Self::Json => "application/json",
+            Self::Csv => "text/csv; charset=utf-8",
+        }
+    }
+}
+
+/**
+ * Builds a [`Response`]
+ *
+ * Unless told otherwise, responses are HTML in danish that browsers have to revalidate before
+ * showing again, since the scores change all the time
+ */
+#[derive(Debug)]
+pub struct ResponseBuilder {
+    status: Status,
+    content_type: ContentType,
+    content_language: &'static str,
+    cache_control: &'static str,
+    headers: Vec<(&'static str, String)>,
+}
+
+impl Response {
+    pub fn builder(status: Status) -> ResponseBuilder {
+        ResponseBuilder {
+            status,
+            content_type: ContentType::Html,
+            content_language: "da",
+            cache_control: "no-cache",
+            headers: Vec::new(),
+        }
+    }
+
+    /// A `303 See Other` redirect to `location`, used after a form has been submitted
+    pub fn redirect(location: impl Into<String>) -> Self {
+        Self::builder(Status::SeeOther)
+            .cache_control("no-store")
+            .header("Location", location)
+            .body("")
+    }
+
+    /// Just the reason phrase as plain text, for requests that can't be handled at all
+    pub fn plain(status: Status) -> Self {
+        Self::builder(status)
+            .content_type(ContentType::Plain)
+            .content_language("en")
+            .body(status.reason())
+    }
+
+    /// Adds a header to an already built response
+    pub fn with_header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// Writes the response in the CGI output format, used by both CGI and FastCGI
+    pub fn write_cgi(&self, w: &mut impl Write) -> io::Result<()> {
+        let status = self.status;
+        write!(w, "Status: {} {}\r\n", status.code(), status.reason())?;
+        self.write_headers_and_body(w)
+    }
+
+    /// Writes the response as HTTP/1.1, the connection is closed afterwards
+    pub fn write_http(&self, w: &mut impl Write) -> io::Result<()> {
+        let status = self.status;
+        write!(w, "HTTP/1.1 {} {}\r\n", status.code(), status.reason())?;
+        write!(w, "Connection: close\r\n")?;
+        self.write_headers_and_body(w)
+    }
+
+    fn write_headers_and_body(&self, w: &mut impl Write) -> io::Result<()> {
+        for (name, value) in &self.headers {
+            write!(w, "{name}: {value}\r\n")?;
+        }
+        write!(w, "\r\n{}", self.body)
+    }
+}
+
+impl ResponseBuilder {
+    pub fn content_type(mut self, content_type: ContentType) -> Self {
+        self.content_type = content_type;
+        self
+    }
+
+    pub fn content_language(mut self, language: &'static str) -> Self {
+        self.content_language = language;
+        self
+    }
+
+    pub fn cache_control(mut self, directives: &'static str) -> Self {
+        self.cache_control = directives;
+        self
+    }
+
+    /// Adds a header that isn't covered by the other methods
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /**
+     * Makes browsers download the response as `filename`
+     *
+     * Non-ASCII characters are only kept in the `filename*` parameter (RFC 6266), older browsers
+     * get a version where they are replaced with '_'
+     */
+    pub fn attachment(self, filename: &str) -> Self {
+        let fallback: String = filename
+            .chars()
+            .map(|c| match c {
+                c if c.is_ascii_alphanumeric() || "-._ ".contains(c) => c,
+                _ => '_',
+            })
+            .collect();
+        let encoded: String = filename
+            .bytes()
+            .map(|b| match b {
+                b if b.is_ascii_alphanumeric() || b"-._~".contains(&b) => (b as char).to_string(),
+                _ => format!("%{b:02X}"),
+            })
+            .collect();
+        self.header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}"),
+        )
+    }
+
+    /// Finishes the response, this is where `Content-Length` gets set
+    pub fn body(self, body: impl Into<String>) -> Response {
+        let body = body.into();
+        let mut headers = vec![
+            ("Content-Type", self.content_type.as_str().to_owned()),
+            ("Content-Length", body.len().to_string()),
+            ("Content-Language", self.content_language.to_owned()),
+            ("Cache-Control", self.cache_control.to_owned()),
+        ];
+        headers.extend(self.headers);
+        Response {
+            status: self.status,
+            headers,
+            body,
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use crate::config::Config;
+use crate::request::Request;
+use crate::response::{ContentType, Response, Status};
+use std::fs;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a client can take to send its whole request, and to receive the whole response
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many connections are handled at once, the others wait until one of them is done
+const MAX_CONNECTIONS: usize = 64;
+
+/**
+ * Serves the frontend over plain HTTP/1.1 on `addr`
+ *
+ * `/u` is routed to [`crate::respond`] exactly like the CGI script would be, and
+ * the static files that lighttpd normally serves are read from `document_root`.
+ * `server` is the URL of the API server and `config` the settings read at startup.
+ * Every connection is handled on its own thread and closed after a single response, or when
+ * the client takes longer than [`TIMEOUT`] to send the request or receive the response, no
+ * matter how little it sends at a time. At most [`MAX_CONNECTIONS`] are handled at once, so
+ * slow clients can hold up the others for a while, but can't make it start any number of
+ * threads.
+ */
+pub fn listen(
+    addr: &str,
+    document_root: PathBuf,
+    server: &'static str,
+    config: &'static Config,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let document_root = Arc::new(document_root);
+    let connections = Arc::new(Slots::new(MAX_CONNECTIONS));
+    eprintln!("Listening on http://{}", listener.local_addr()?);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Failed to accept connection: {e}");
+                continue;
+            }
+        };
+        let document_root = Arc::clone(&document_root);
+        let slot = Slots::take(&connections);
+        thread::spawn(move || {
+            let _slot = slot;
+            if let Err(e) = handle(stream, &document_root, server, config) {
+                eprintln!("Failed to handle connection: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle(
+    stream: TcpStream,
+    document_root: &Path,
+    server: &str,
+    config: &Config,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(Deadline::after(TIMEOUT, stream.try_clone()?));
+
+    let response = match Request::from_http(&mut reader) {
+        Ok(mut request) => {
+            request.remote_addr = stream.peer_addr()?.ip().to_string();
+            route(&request, document_root, server, config)
+        }
+        Err(e) if e.kind() == io::ErrorKind::FileTooLarge => {
+            Response::plain(Status::ContentTooLarge)
+        }
+        // The client is gone or too slow, there's no one to respond to
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            return Ok(())
+        }
+        Err(_) => Response::plain(Status::BadRequest),
+    };
+    let mut stream = Deadline::after(TIMEOUT, stream);
+    response.write_http(&mut stream)?;
+    stream.flush()
+}
+
+/**
+ * A connection that fails with [`io::ErrorKind::TimedOut`] after a point in time
+ *
+ * The timeouts of [`TcpStream`] only apply to each read or write, which a client can stay under
+ * by sending a byte at a time, so they're set to the time that's left before every one.
+ */
+struct Deadline {
+    stream: TcpStream,
+    until: Instant,
+}
+
+impl Deadline {
+    fn after(timeout: Duration, stream: TcpStream) -> Self {
+        Self {
+            stream,
+            until: Instant::now() + timeout,
+        }
+    }
+
+    /// The time that's left, or an error if there's none
+    fn left(&self) -> io::Result<Option<Duration>> {
+        match self.until.saturating_duration_since(Instant::now()) {
+            Duration::ZERO => Err(io::Error::new(io::ErrorKind::TimedOut, "client too slow")),
+            left => Ok(Some(left)),
+        }
+    }
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.set_read_timeout(self.left()?)?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for Deadline {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.set_write_timeout(self.left()?)?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Counts the connections being handled, see [`MAX_CONNECTIONS`]
+struct Slots {
+    free: Mutex<usize>,
+    freed: Condvar,
+}
+
+/// One of the [`Slots`], given back when it's dropped
+struct Slot(Arc<Slots>);
+
+impl Slots {
+    fn new(count: usize) -> Self {
+        Self {
+            free: Mutex::new(count),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Waits until a slot is free and takes it
+    fn take(slots: &Arc<Self>) -> Slot {
+        let free = slots.free.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut free = slots
+            .freed
+            .wait_while(free, |free| *free == 0)
+            .unwrap_or_else(PoisonError::into_inner);
+        *free -= 1;
+        Slot(Arc::clone(slots))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        *self.0.free.lock().unwrap_or_else(PoisonError::into_inner) += 1;
+        self.0.freed.notify_one();
+    }
+}
+
+fn route(request: &Request, document_root: &Path, server: &str, config: &Config) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET" | "POST", "/u") => crate::respond(request, server, config),
+        ("GET", "/user.css") => static_file(document_root, "user.css", ContentType::Css),
+        // Only kept to send links from before the form was rendered by `/u` on to it
+        ("GET", path @ ("/submit_score.html" | "/submit_score.en.html")) => {
+            static_file(document_root, &path[1..], ContentType::Html)
+        }
+        (_, "/u" | "/user.css" | "/submit_score.html" | "/submit_score.en.html") => {
+            Response::plain(Status::MethodNotAllowed)
+        }
+        _ => Response::plain(Status::NotFound),
+    }
+}
+
+fn static_file(document_root: &Path, name: &str, content_type: ContentType) -> Response {
+    match fs::read_to_string(document_root.join(name)) {
+        Ok(content) => Response::builder(Status::Ok)
+            .content_type(content_type)
+            .cache_control("max-age=3600")
+            .body(content),
+        Err(e) => {
+            eprintln!(
+                "Failed to read {name} from {}: {e}",
+                document_root.display()
+            );
+            Response::plain(Status::NotFound)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Both ends of a TCP connection
+    fn connection() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        (client, listener.accept().unwrap().0)
+    }
+
+    #[test]
+    fn clients_sending_a_byte_at_a_time_time_out() {
+        let (mut client, server) = connection();
+        thread::spawn(move || {
+            while client.write_all(b"G").is_ok() {
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+
+        let started = Instant::now();
+        let mut reader = BufReader::new(Deadline::after(Duration::from_millis(300), server));
+        let error = Request::from_http(&mut reader).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn connections_wait_for_a_free_slot() {
+        let slots = Arc::new(Slots::new(1));
+        let slot = Slots::take(&slots);
+        let taken = Arc::new(AtomicBool::new(false));
+
+        let waiting = thread::spawn({
+            let (slots, taken) = (Arc::clone(&slots), Arc::clone(&taken));
+            move || {
+                let _slot = Slots::take(&slots);
+                taken.store(true, Ordering::SeqCst);
+            }
+        });
+        thread::sleep(Duration::from_millis(100));
+        assert!(!taken.load(Ordering::SeqCst));
+
+        drop(slot);
+        waiting.join().unwrap();
+        assert!(taken.load(Ordering::SeqCst));
+    }
+}
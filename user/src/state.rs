@@ -0,0 +1,274 @@
+use crate::csrf;
+use crate::error::Error;
+use hmac_sha256::Hash;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/**
+ * The directory where things that have to survive between requests are kept
+ *
+ * Set with `STATE_DIR`, it defaults to `/var/tmp/bullseyegolf-light` and is created when it's
+ * first used. In CGI mode every request is a new process, so this is the only place to keep them.
+ */
+#[derive(Debug, Clone)]
+pub struct Store {
+    dir: PathBuf,
+    /// The values of [`Store::cache`] by namespace and key, with when they were stored
+    memory: Option<Arc<Mutex<Memory>>>,
+}
+
+type Memory = HashMap<(String, String), (Instant, String)>;
+
+type Namespace = HashMap<String, Entry>;
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    value: String,
+    /// Seconds since the unix epoch
+    expires: u64,
+}
+
+impl Store {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            memory: None,
+        }
+    }
+
+    /// The store in `STATE_DIR`
+    pub fn from_env() -> Self {
+        let dir = env::var_os("STATE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("/var/tmp/bullseyegolf-light"));
+        Self::new(dir)
+    }
+
+    /**
+     * The same store, but with [`Store::cache`] only in memory, shared by all its clones
+     *
+     * For the resident modes, where the next request is handled by the same process. The rest
+     * is still kept in [`Store::dir`], since there can be several of them sharing a `STATE_DIR`.
+     */
+    pub fn with_memory_cache(self) -> Self {
+        Self {
+            memory: Some(Arc::default()),
+            ..self
+        }
+    }
+
+    /// The directory, created if it doesn't exist yet
+    pub fn dir(&self) -> Result<&Path, Error> {
+        fs::create_dir_all(&self.dir)?;
+        Ok(&self.dir)
+    }
+
+    /**
+     * Changes the value of `key` in `namespace`, a small key-value store kept between requests
+     *
+     * `f` can change the value, set it to [`None`] to remove it, and return anything. If it's
+     * changed it expires in `ttl` seconds, so the stores don't grow forever.
+     *
+     * Every namespace is a file in [`Store::dir`], which is locked until `f` returns, so concurrent
+     * requests don't overwrite each other. The resident modes use the files too, since there can be
+     * several of them (or CGI processes) sharing a `STATE_DIR`. A namespace with `/` in it is a
+     * file in a subdirectory, for stores that would be too big as a single file.
+     */
+    pub fn update<T>(
+        &self,
+        namespace: &str,
+        key: &str,
+        ttl: u64,
+        f: impl FnOnce(&mut Option<String>) -> T,
+    ) -> Result<T, Error> {
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.namespace_path(namespace, true)?)?;
+        file.lock()?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        let mut entries: Namespace = match content.is_empty() {
+            true => Namespace::new(),
+            false => serde_json::from_str(&content)?,
+        };
+
+        let now = now()?;
+        let count = entries.len();
+        entries.retain(|_, e| e.expires > now);
+        let before = entries.get(key).map(|e| e.value.clone());
+        let mut value = before.clone();
+        let result = f(&mut value);
+        let changed = value != before || entries.len() != count;
+        if value != before {
+            match value {
+                Some(value) => {
+                    let expires = now + ttl;
+                    entries.insert(key.to_owned(), Entry { value, expires });
+                }
+                None => {
+                    entries.remove(key);
+                }
+            }
+        }
+        if changed {
+            file.set_len(0)?;
+            file.rewind()?;
+            file.write_all(serde_json::to_string(&entries)?.as_bytes())?;
+        }
+        Ok(result)
+    }
+
+    /**
+     * The value of `key` in `namespace`, see [`Store::update`]
+     *
+     * Only takes a shared lock, so reads don't wait for each other
+     */
+    pub fn get(&self, namespace: &str, key: &str) -> Result<Option<String>, Error> {
+        let mut file = match File::open(self.namespace_path(namespace, false)?) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        file.lock_shared()?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        if content.is_empty() {
+            return Ok(None);
+        }
+        let mut entries: Namespace = serde_json::from_str(&content)?;
+        let now = now()?;
+        Ok(entries
+            .remove(key)
+            .filter(|e| e.expires > now)
+            .map(|e| e.value))
+    }
+
+    /// The file of `namespace`, with the directories it's in if `create` is true
+    fn namespace_path(&self, namespace: &str, create: bool) -> Result<PathBuf, Error> {
+        let path = self.dir()?.join(format!("{namespace}.json"));
+        if let (true, Some(parent)) = (create, path.parent()) {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(path)
+    }
+
+    /**
+     * The value stored for `key` in `namespace` with [`Store::cache`], if it's less than `ttl`
+     * seconds old
+     *
+     * Unlike [`Store::update`] these are values that are read often and are too big to keep
+     * together. With [`Store::with_memory_cache`] they're only kept in memory, otherwise every key
+     * is a file of its own in a directory in [`Store::dir`]. The files are only ever replaced as
+     * a whole, so they can be read without a lock.
+     */
+    pub fn cached(&self, namespace: &str, key: &str, ttl: u64) -> Result<Option<String>, Error> {
+        if let Some(memory) = &self.memory {
+            let memory = memory.lock().unwrap_or_else(PoisonError::into_inner);
+            return Ok(memory
+                .get(&(namespace.to_owned(), key.to_owned()))
+                .filter(|(stored, _)| stored.elapsed().as_secs() < ttl)
+                .map(|(_, value)| value.clone()));
+        }
+
+        let path = self.cache_path(namespace, key)?;
+        let age = match fs::metadata(&path).and_then(|m| m.modified()) {
+            Ok(modified) => modified.elapsed().unwrap_or_default(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if age.as_secs() >= ttl {
+            return Ok(None);
+        }
+        match fs::read_to_string(&path) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /**
+     * Stores `value` for `key` in `namespace`, see [`Store::cached`]
+     *
+     * Values in `namespace` that are `ttl` seconds old are removed at the same time, so the cache
+     * only grows with the number of keys used within `ttl`.
+     */
+    pub fn cache(&self, namespace: &str, key: &str, value: &str, ttl: u64) -> Result<(), Error> {
+        if let Some(memory) = &self.memory {
+            let mut memory = memory.lock().unwrap_or_else(PoisonError::into_inner);
+            memory.retain(|(n, _), (stored, _)| n != namespace || stored.elapsed().as_secs() < ttl);
+            let key = (namespace.to_owned(), key.to_owned());
+            memory.insert(key, (Instant::now(), value.to_owned()));
+            return Ok(());
+        }
+
+        let path = self.cache_path(namespace, key)?;
+        // Unique, since several threads or processes can store the same key at once
+        let temporary = path.with_extension(format!("{}.tmp", csrf::random_hex(8)?));
+        fs::write(&temporary, value)?;
+        fs::rename(&temporary, &path).inspect_err(|_| {
+            let _ = fs::remove_file(&temporary);
+        })?;
+        remove_expired(path.parent().unwrap_or(&self.dir), ttl)
+    }
+
+    /// Forgets the value for `key` in `namespace`, see [`Store::cached`]
+    pub fn uncache(&self, namespace: &str, key: &str) -> Result<(), Error> {
+        if let Some(memory) = &self.memory {
+            let mut memory = memory.lock().unwrap_or_else(PoisonError::into_inner);
+            memory.remove(&(namespace.to_owned(), key.to_owned()));
+            return Ok(());
+        }
+
+        match fs::remove_file(self.cache_path(namespace, key)?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The file for `key` in `namespace` is named after a hash of it, since keys can be anything
+    fn cache_path(&self, namespace: &str, key: &str) -> Result<PathBuf, Error> {
+        let dir = self.dir()?.join(namespace);
+        fs::create_dir_all(&dir)?;
+        let hash: String = Hash::hash(key.as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        Ok(dir.join(hash))
+    }
+}
+
+/**
+ * Removes the files in `dir` that were last changed `ttl` seconds ago or earlier
+ *
+ * That includes temporary files left behind by processes that were killed. Another process
+ * could be removing the same files, so ones that are gone already are skipped.
+ */
+fn remove_expired(dir: &Path, ttl: u64) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let expired = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| modified.elapsed().unwrap_or_default().as_secs() >= ttl);
+        if expired {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Seconds since the unix epoch
+pub fn now() -> Result<u64, Error> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
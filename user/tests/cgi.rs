@@ -0,0 +1,767 @@
+//! Drives the CGI script end to end against the mock API server
+
+mod common;
+
+use common::mock_api::Failure;
+use common::{Cgi, MockApi};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+
+/// The form for submitting a score to hole 7 of the summer cup, with the token and key
+fn submission(cgi: &Cgi, fields: &str) -> String {
+    let form = cgi.get("u=club&t=summer&h=7&view=submit");
+    assert_eq!(form.status, 200, "{}", form.body);
+    format!(
+        "token={}&key={}&{fields}",
+        form.hidden_input("token"),
+        form.hidden_input("key")
+    )
+}
+
+#[test]
+fn select_tournament_page() {
+    let api = MockApi::start();
+    let response = Cgi::new(&api, "select_tournament_page").get("u=club&lang=en");
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("Content-Language"), Some("en"));
+    let (active, finished) = response.body.split_once("Finished tournaments").unwrap();
+    assert!(active.contains(r#"<a href="?u=club&amp;t=summer">Summer Cup</a>"#));
+    assert!(finished.contains(r#"<a href="?u=club&amp;t=winter">Winter Cup</a>"#));
+    // Finished too long ago
+    assert!(!response.body.contains("Spring Cup"));
+}
+
+#[test]
+fn select_hole_page() {
+    let api = MockApi::start();
+    let response = Cgi::new(&api, "select_hole_page").get("u=club&t=summer");
+
+    assert_eq!(response.status, 200);
+    assert!(response.body.contains("Summer Cup"));
+    assert!(response
+        .body
+        .contains(r#"href="?u=club&amp;t=summer&amp;h=3""#));
+    assert!(response
+        .body
+        .contains(r#"href="?u=club&amp;t=summer&amp;h=7""#));
+}
+
+#[test]
+fn view_hole_page() {
+    let api = MockApi::start();
+    let response = Cgi::new(&api, "view_hole_page").get("u=club&t=summer&h=3&lang=en");
+
+    assert_eq!(response.status, 200);
+    let body = &response.body;
+    assert!(body.contains("<h1 id=\"title\">Hole 3</h1>"));
+    assert!(body.contains("Sponsored by: The Shop"));
+    assert!(body.contains("<td>1.</td><td>Anna</td><td>123-4</td><td>1.25m</td>"));
+    // The legacy "🏴" suffix is shown as the marker instead
+    assert!(body.contains("<td>Bo<span class=\"not-leading\""));
+    assert!(body.contains(r#"href="?u=club&amp;t=summer&amp;h=3&amp;view=submit""#));
+}
+
+#[test]
+fn scores_are_shown_and_submitted_without_the_not_leading_flags() {
+    let api = MockApi::start();
+    let test = "scores_are_shown_and_submitted_without_the_not_leading_flags";
+    let cgi = Cgi::new(&api, test);
+    // A file where the directory of the store should be
+    let state_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(test);
+    fs::create_dir_all(&state_dir).unwrap();
+    fs::write(state_dir.join("not_leading"), "").unwrap();
+
+    let response = cgi.get("u=club&t=summer&h=3");
+    assert_eq!(response.status, 200, "{}", response.body);
+    assert!(response.body.contains("<td>Bo<span class=\"not-leading\""));
+
+    let form = submission(&cgi, "name=Dora&score_m=3&score_cm=5");
+    assert_eq!(cgi.post("u=club&t=summer&h=7", &form, &[]).status, 303);
+    assert_eq!(api.scores("club", "summer", 7).len(), 1);
+}
+
+#[test]
+fn scores_submitted_while_not_leading_are_flagged() {
+    let api = MockApi::start();
+    let test = "scores_submitted_while_not_leading_are_flagged";
+    let cgi = Cgi::new(&api, test);
+    let form = cgi.get("u=club&t=summer&h=3&view=submit");
+    let form = format!(
+        "token={}&key={}&name=Dora&score_m=3&score_cm=5",
+        form.hidden_input("token"),
+        form.hidden_input("key")
+    );
+    assert_eq!(cgi.post("u=club&t=summer&h=3", &form, &[]).status, 303);
+
+    for query in ["u=club&t=summer&h=3", "u=club&t=summer&view=all"] {
+        let response = cgi.get(query);
+        assert!(response
+            .body
+            .contains("<td>Dora<span class=\"not-leading\""));
+    }
+    // Kept with the hole, not with every other hole
+    let store = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{test}/not_leading"));
+    let holes: Vec<_> = fs::read_dir(store.join("club/summer"))
+        .unwrap()
+        .map(|f| f.unwrap().file_name())
+        .collect();
+    assert_eq!(holes, ["3.json"]);
+}
+
+#[test]
+fn view_hole_page_of_finished_tournament() {
+    let api = MockApi::start();
+    let response =
+        Cgi::new(&api, "view_hole_page_of_finished_tournament").get("u=club&t=winter&h=1");
+
+    assert_eq!(response.status, 200);
+    assert!(response.body.contains("Carl"));
+    assert!(!response.body.contains("view=submit"));
+}
+
+#[test]
+fn leaderboard_page() {
+    let api = MockApi::start();
+    let response = Cgi::new(&api, "leaderboard_page").get("u=club&t=summer&view=all&n=1");
+
+    assert_eq!(response.status, 200);
+    assert!(response.body.contains("<td>Anna</td>"));
+    assert!(!response.body.contains("<td>Bo"));
+}
+
+#[test]
+fn player_page() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(&api, "player_page");
+
+    let response = cgi.get("u=club&t=summer&view=player&p=anna");
+    assert_eq!(response.status, 200);
+    assert!(response
+        .body
+        .contains(r#"href="?u=club&amp;t=summer&amp;h=3""#));
+
+    let response = cgi.get("u=club&t=summer&view=player&p=anna&m=999");
+    assert_eq!(response.status, 200);
+    assert!(!response
+        .body
+        .contains(r#"href="?u=club&amp;t=summer&amp;h=3""#));
+}
+
+#[test]
+fn submit_score_page() {
+    let api = MockApi::start();
+    let response = Cgi::new(&api, "submit_score_page").get("u=club&t=summer&h=7&view=submit");
+
+    assert_eq!(response.status, 200);
+    assert!(response
+        .body
+        .contains(r#"action="?u=club&amp;t=summer&amp;h=7""#));
+    assert!(!response.hidden_input("token").is_empty());
+    assert_eq!(response.hidden_input("key").len(), 32);
+}
+
+#[test]
+fn json_and_csv() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(&api, "json_and_csv");
+
+    let response = cgi.get("u=club&t=summer&h=3&format=json");
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("Content-Type"), Some("application/json"));
+    let hole: Value = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(
+        hole["scores"][0],
+        json!({ "rank": 1, "player_name": "Anna", "member_number": "123-4", "player_score": 1.25 })
+    );
+    assert_eq!(hole["active"], json!(true));
+
+    let response = cgi.get_with("u=club&t=summer&h=3", &[("Accept", "text/csv")]);
+    assert_eq!(response.status, 200);
+    assert!(response.body.contains("1;Anna;123-4;1,25\r\n"));
+}
+
+#[test]
+fn member_numbers_can_be_hidden() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(&api, "member_numbers_can_be_hidden").env("MEMBER_NUMBERS", "hide");
+
+    let response = cgi.get("u=club&t=summer&h=3");
+    assert!(response.body.contains("<td>Anna</td>"));
+    assert!(!response.body.contains("123-4"));
+
+    // Guessing a member number doesn't tell whether it's Anna's
+    for m in ["123-4", "999"] {
+        let response = cgi.get(&format!(
+            "u=club&t=summer&view=player&p=anna&m={m}&format=json"
+        ));
+        let player: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(player.get("member"), None);
+        assert_eq!(player["scores"].as_array().unwrap().len(), 1);
+    }
+}
+
+#[test]
+fn names_starting_with_a_single_digit_are_not_member_numbers() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(
+        &api,
+        "names_starting_with_a_single_digit_are_not_member_numbers",
+    );
+    let form = submission(&cgi, "name=2+Putt&score_m=3&score_cm=5");
+    assert_eq!(cgi.post("u=club&t=summer&h=7", &form, &[]).status, 303);
+
+    let response = cgi.get("u=club&t=summer&h=7&format=json");
+    let hole: Value = serde_json::from_str(&response.body).unwrap();
+    let score = &hole["scores"][0];
+    assert_eq!(score["player_name"], "2 Putt");
+    assert_eq!(score["member_number"], Value::Null);
+}
+
+#[test]
+fn names_starting_with_two_digits_keep_them() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(&api, "names_starting_with_two_digits_keep_them");
+    // Submitted to different holes, so both are first
+    for (hole, fields) in [(3, "name=10+Pins"), (7, "name=10+Pins&member=55")] {
+        let form = cgi.get(&format!("u=club&t=summer&h={hole}&view=submit"));
+        let form = format!(
+            "token={}&key={}&{fields}&score_m=0&score_cm=5",
+            form.hidden_input("token"),
+            form.hidden_input("key")
+        );
+        let response = cgi.post(&format!("u=club&t=summer&h={hole}"), &form, &[]);
+        assert_eq!(response.status, 303);
+    }
+
+    // With a no-break space, which looks the same
+    for (hole, member) in [(3, Value::Null), (7, json!("55"))] {
+        let response = cgi.get(&format!("u=club&t=summer&h={hole}&format=json"));
+        let hole: Value = serde_json::from_str(&response.body).unwrap();
+        let score = &hole["scores"][0];
+        assert_eq!(score["player_name"], "10\u{a0}Pins");
+        assert_eq!(score["member_number"], member);
+    }
+    let response = cgi.get("u=club&t=summer&view=player&p=10+pins&format=json");
+    let player: Value = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(player["scores"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn member_numbers_are_stored_without_a_separator_at_the_end() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(
+        &api,
+        "member_numbers_are_stored_without_a_separator_at_the_end",
+    );
+    let form = submission(&cgi, "name=Dora&member=12-&score_m=3&score_cm=5");
+    assert_eq!(cgi.post("u=club&t=summer&h=7", &form, &[]).status, 303);
+    assert_eq!(api.scores("club", "summer", 7)[0]["player_name"], "12 Dora");
+
+    let response = cgi.get("u=club&t=summer&h=7&format=json");
+    let hole: Value = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(hole["scores"][0]["member_number"], "12");
+    assert_eq!(hole["scores"][0]["player_name"], "Dora");
+}
+
+#[test]
+fn submitting_redirects_to_the_hole() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(&api, "submitting_redirects_to_the_hole");
+    let form = submission(&cgi, "name=Dora&member=55&score_m=3&score_cm=5");
+
+    let response = cgi.post("u=club&t=summer&h=7", &form, &[]);
+    assert_eq!(response.status, 303, "{}", response.body);
+    assert_eq!(response.header("Location"), Some("?u=club&t=summer&h=7"));
+    // The API server can't delete scores unless `SCORE_CHANGES=on`
+    assert_eq!(response.cookie("edit"), None);
+    assert_eq!(
+        api.scores("club", "summer", 7),
+        [json!({ "player_name": "55 Dora", "player_score": 3.05 })]
+    );
+
+    // Submitting the same form again doesn't add the score twice
+    let response = cgi.post("u=club&t=summer&h=7", &form, &[]);
+    assert_eq!(response.status, 303);
+    assert_eq!(api.scores("club", "summer", 7).len(), 1);
+}
+
+/// Deliberately, see `post::Params::new`
+#[test]
+fn the_hole_is_not_taken_from_the_referer() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(&api, "the_hole_is_not_taken_from_the_referer");
+    let form = submission(&cgi, "name=Dora&score_m=3&score_cm=5");
+
+    let referer = [("Referer", "https://example.org/u?u=club&t=summer&h=7")];
+    let response = cgi.post("", &form, &referer);
+    assert_eq!(response.status, 400, "{}", response.body);
+    assert!(api.scores("club", "summer", 7).is_empty());
+}
+
+#[test]
+fn forms_only_work_in_the_browser_they_were_shown_in() {
+    let api = MockApi::start();
+    let test = "forms_only_work_in_the_browser_they_were_shown_in";
+    let cgi = Cgi::new(&api, test).env("CSRF_SECRET", "secret");
+    let form = submission(&cgi, "name=Dora&score_m=3&score_cm=5");
+
+    let other = Cgi::new(&api, test)
+        .env("CSRF_SECRET", "secret")
+        .csrf_cookie(Some("csrf=fedcba9876543210fedcba9876543210"));
+    assert_eq!(other.post("u=club&t=summer&h=7", &form, &[]).status, 403);
+    let without = Cgi::new(&api, test)
+        .env("CSRF_SECRET", "secret")
+        .csrf_cookie(None);
+    assert_eq!(without.post("u=club&t=summer&h=7", &form, &[]).status, 403);
+    assert!(api.scores("club", "summer", 7).is_empty());
+
+    assert_eq!(cgi.post("u=club&t=summer&h=7", &form, &[]).status, 303);
+}
+
+#[test]
+fn the_form_sets_the_csrf_cookie() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(&api, "the_form_sets_the_csrf_cookie");
+    let form = cgi.get("u=club&t=summer&h=7&view=submit");
+    assert_eq!(form.cookie("csrf"), None);
+
+    let form = cgi.csrf_cookie(None).get("u=club&t=summer&h=7&view=submit");
+    let cookie = form.cookie("csrf").expect("no csrf cookie");
+    assert_eq!(cookie.len(), "csrf=".len() + 32);
+}
+
+#[test]
+fn invalid_scores_show_the_form_again() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(&api, "invalid_scores_show_the_form_again");
+    let form = submission(&cgi, "name=D&score_m=99&score_cm=5&lang=en");
+
+    let response = cgi.post("u=club&t=summer&h=7&lang=en", &form, &[]);
+    assert_eq!(response.status, 422);
+    assert!(response
+        .body
+        .contains("The name has to be between 2 and 40 characters long"));
+    assert!(response
+        .body
+        .contains("Meters has to be a whole number from 0 to 25"));
+    assert!(response.body.contains(r#"value="99""#));
+    assert!(api.scores("club", "summer", 7).is_empty());
+}
+
+#[test]
+fn changing_and_withdrawing_a_score() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(&api, "changing_and_withdrawing_a_score").env("SCORE_CHANGES", "on");
+    let form = submission(&cgi, "name=Dora&score_m=3&score_cm=5");
+    let response = cgi.post("u=club&t=summer&h=7", &form, &[]);
+    let cookie = [("Cookie", response.cookie("edit").unwrap())];
+
+    let page = cgi.get_with("u=club&t=summer&h=7", &cookie);
+    assert!(page
+        .body
+        .contains(r#"href="?u=club&amp;t=summer&amp;h=7&amp;view=edit""#));
+
+    let page = cgi.get_with("u=club&t=summer&h=7&view=edit", &cookie);
+    assert_eq!(page.status, 200);
+    assert!(page.body.contains(r#"value="Dora""#));
+    let (token, edit) = (page.hidden_input("token"), page.hidden_input("edit"));
+
+    let form = format!("token={token}&edit={edit}&name=Dora&score_m=2&score_cm=0");
+    let response = cgi.post("u=club&t=summer&h=7", &form, &cookie);
+    assert_eq!(response.status, 303, "{}", response.body);
+    assert_eq!(
+        api.scores("club", "summer", 7),
+        [json!({ "player_name": "Dora", "player_score": 2.0 })]
+    );
+
+    let form = format!("token={token}&edit={edit}&withdraw=1");
+    let response = cgi.post("u=club&t=summer&h=7", &form, &cookie);
+    assert_eq!(response.status, 303, "{}", response.body);
+    assert!(api.scores("club", "summer", 7).is_empty());
+    assert_eq!(response.cookie("edit"), Some("edit="));
+}
+
+#[test]
+fn scores_can_only_be_changed_when_turned_on() {
+    let api = MockApi::start();
+    let on = Cgi::new(&api, "scores_can_only_be_changed_when_turned_on").env("SCORE_CHANGES", "on");
+    let form = submission(&on, "name=Dora&score_m=3&score_cm=5");
+    let response = on.post("u=club&t=summer&h=7", &form, &[]);
+    let cookie = [("Cookie", response.cookie("edit").unwrap())];
+    let page = on.get_with("u=club&t=summer&h=7&view=edit", &cookie);
+    assert_eq!(page.status, 200);
+    let form = format!(
+        "token={}&edit={}&withdraw=1",
+        page.hidden_input("token"),
+        page.hidden_input("edit")
+    );
+
+    // A cookie from before it was turned off doesn't allow it anymore
+    let off = on.env("SCORE_CHANGES", "off");
+    let page = off.get_with("u=club&t=summer&h=7", &cookie);
+    assert!(!page.body.contains("view=edit"));
+    assert_eq!(
+        off.get_with("u=club&t=summer&h=7&view=edit", &cookie)
+            .status,
+        403
+    );
+    assert_eq!(off.post("u=club&t=summer&h=7", &form, &cookie).status, 403);
+    assert_eq!(api.scores("club", "summer", 7).len(), 1);
+}
+
+#[test]
+fn a_failed_change_keeps_the_old_score() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(&api, "a_failed_change_keeps_the_old_score").env("SCORE_CHANGES", "on");
+    let form = submission(&cgi, "name=Dora&score_m=3&score_cm=5");
+    let response = cgi.post("u=club&t=summer&h=7", &form, &[]);
+    let cookie = [("Cookie", response.cookie("edit").unwrap())];
+    let page = cgi.get_with("u=club&t=summer&h=7&view=edit", &cookie);
+    let (token, edit) = (page.hidden_input("token"), page.hidden_input("edit"));
+
+    api.fail_with(Failure::RejectNextPost);
+    let form = format!("token={token}&edit={edit}&name=Dora&score_m=2&score_cm=0");
+    let response = cgi.post("u=club&t=summer&h=7", &form, &cookie);
+    assert_eq!(response.status, 502, "{}", response.body);
+    assert_eq!(
+        api.scores("club", "summer", 7),
+        [json!({ "player_name": "Dora", "player_score": 3.05 })]
+    );
+}
+
+#[test]
+fn responses_are_cached_until_a_score_is_submitted() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(&api, "responses_are_cached_until_a_score_is_submitted")
+        .env("CACHE_SECONDS", "60");
+
+    assert_eq!(cgi.get("u=club&t=summer&h=7").status, 200);
+    assert_eq!(cgi.get("u=club&t=summer&view=all").status, 200);
+    let requests = api.requests();
+    assert_eq!(cgi.get("u=club&t=summer&h=7").status, 200);
+    assert_eq!(cgi.get("u=club&t=summer&view=all").status, 200);
+    assert_eq!(api.requests(), requests);
+
+    let form = submission(&cgi, "name=Dora&score_m=3&score_cm=5");
+    assert_eq!(cgi.post("u=club&t=summer&h=7", &form, &[]).status, 303);
+    assert!(cgi.get("u=club&t=summer&h=7").body.contains("Dora"));
+    assert!(cgi.get("u=club&t=summer&view=all").body.contains("Dora"));
+}
+
+#[test]
+fn caching_can_be_turned_off() {
+    let api = MockApi::start();
+    let cgi = Cgi::new(&api, "caching_can_be_turned_off").env("CACHE_SECONDS", "0");
+
+    assert_eq!(cgi.get("u=club&t=summer&h=7").status, 200);
+    let requests = api.requests();
+    assert_eq!(cgi.get("u=club&t=summer&h=7").status, 200);
+    assert!(api.requests() > requests);
+}
+
+#[test]
+fn expired_responses_are_removed_from_the_cache() {
+    let api = MockApi::start();
+    let test = "expired_responses_are_removed_from_the_cache";
+    let cgi = Cgi::new(&api, test).env("CACHE_SECONDS", "1");
+    let cache = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{test}/api_cache"));
+    let files = || fs::read_dir(&cache).unwrap().count();
+
+    assert_eq!(cgi.get("u=club&t=summer&h=7").status, 200);
+    assert_eq!(cgi.get("u=club&t=summer&h=3").status, 200);
+    assert!(files() > 1);
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    // Only needs the list of tournaments
+    assert_eq!(cgi.get("u=club").status, 200);
+    assert_eq!(files(), 1);
+}
+
+#[test]
+fn pages_are_served_when_the_cache_is_broken() {
+    let api = MockApi::start();
+    let test = "pages_are_served_when_the_cache_is_broken";
+    let cgi = Cgi::new(&api, test).env("CACHE_SECONDS", "60");
+    // A file where the directory of the cache should be
+    let state_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(test);
+    fs::create_dir_all(&state_dir).unwrap();
+    fs::write(state_dir.join("api_cache"), "").unwrap();
+
+    assert_eq!(cgi.get("u=club&t=summer&h=7").status, 200);
+    let form = submission(&cgi, "name=Dora&score_m=3&score_cm=5");
+    assert_eq!(cgi.post("u=club&t=summer&h=7", &form, &[]).status, 303);
+    assert_eq!(api.scores("club", "summer", 7).len(), 1);
+}
+
+#[test]
+fn offline_demo_file() {
+    let file = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("offline_demo_file.json");
+    fs::copy(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../server/demo.json"),
+        &file,
+    )
+    .unwrap();
+    let cgi = Cgi::with_server(&format!("file://{}", file.display()), "offline_demo_file");
+
+    let page = cgi.get("u=demo&t=sommer&h=3");
+    assert_eq!(page.status, 200, "{}", page.body);
+    assert!(page.body.contains("Mette Hansen"));
+
+    let form = cgi.get("u=demo&t=sommer&h=14&view=submit");
+    let form = format!(
+        "token={}&key={}&name=Dora&score_m=3&score_cm=5",
+        form.hidden_input("token"),
+        form.hidden_input("key")
+    );
+    let response = cgi.post("u=demo&t=sommer&h=14", &form, &[]);
+    assert_eq!(response.status, 303, "{}", response.body);
+    let data: Value = serde_json::from_str(&fs::read_to_string(&file).unwrap()).unwrap();
+    assert_eq!(
+        data["demo"]["sommer"]["holes"][2]["scores"],
+        json!([{ "player_name": "Dora", "player_score": 3.05 }])
+    );
+    assert!(!file.with_extension("json.tmp").exists());
+}
+
+#[test]
+fn concurrent_submissions_to_the_demo_file() {
+    let file = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("concurrent_demo_file.json");
+    fs::copy(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../server/demo.json"),
+        &file,
+    )
+    .unwrap();
+    let cgi = Cgi::with_server(
+        &format!("file://{}", file.display()),
+        "concurrent_submissions_to_the_demo_file",
+    );
+
+    let forms: Vec<String> = (1..=8)
+        .map(|m| {
+            let form = cgi.get("u=demo&t=sommer&h=14&view=submit");
+            format!(
+                "token={}&key={}&name=Player+{m}&score_m={m}&score_cm=0",
+                form.hidden_input("token"),
+                form.hidden_input("key")
+            )
+        })
+        .collect();
+    std::thread::scope(|scope| {
+        for form in &forms {
+            let cgi = &cgi;
+            scope.spawn(move || {
+                let response = cgi.post("u=demo&t=sommer&h=14", form, &[]);
+                assert_eq!(response.status, 303, "{}", response.body);
+            });
+        }
+    });
+
+    let data: Value = serde_json::from_str(&fs::read_to_string(&file).unwrap()).unwrap();
+    let scores = data["demo"]["sommer"]["holes"][2]["scores"]
+        .as_array()
+        .unwrap();
+    assert_eq!(scores.len(), forms.len());
+}
+
+/// Every [`Error`] variant, with the status and message it's shown with
+mod errors {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    fn assert_error(response: common::CgiResponse, status: u16, message: &str) {
+        assert_eq!(response.status, status, "{}", response.body);
+        assert_eq!(response.header("Cache-Control"), Some("no-store"));
+        assert!(response.body.contains(message), "{}", response.body);
+    }
+
+    #[test]
+    fn invalid_query_string() {
+        let api = MockApi::start();
+        let response = Cgi::new(&api, "invalid_query_string").get("lang=en");
+        assert_error(response, 400, "There is an error in the URL");
+    }
+
+    #[test]
+    fn ids_that_are_not_in_the_api_url() {
+        let api = MockApi::start();
+        let cgi = Cgi::new(&api, "ids_that_are_not_in_the_api_url");
+        // The API URL ends at the `#`, so the rest would only end up in the links
+        for query in [
+            "u=club&t=summer%23%22%3E%3Cscript%3Ealert(1)%3C/script%3E&view=all",
+            "u=club&t=summer%23%22%3E%3Cscript%3Ealert(1)%3C/script%3E",
+            "u=club%23%22%3E%3Cscript%3Ealert(1)%3C/script%3E",
+            "u=club&t=summer%2F..&h=3",
+        ] {
+            let response = cgi.get(&format!("{query}&lang=en"));
+            assert!(!response.body.contains("<script>"), "{}", response.body);
+            assert_error(response, 400, "There is an error in the URL");
+        }
+    }
+
+    #[test]
+    fn network() {
+        let api = MockApi::start();
+        api.fail_with(Failure::TruncatedBody);
+        let response = Cgi::new(&api, "network").get("u=club&lang=en");
+        assert_error(response, 500, "An unknown network error happened");
+    }
+
+    #[test]
+    fn submitting_without_a_hole() {
+        let api = MockApi::start();
+        let response =
+            Cgi::new(&api, "submitting_without_a_hole").post("lang=en", "name=Dora", &[]);
+        assert_error(response, 400, "There is an error in the URL");
+    }
+
+    #[test]
+    fn invalid_form() {
+        let api = MockApi::start();
+        let response = Cgi::new(&api, "invalid_form").post(
+            "u=club&t=summer&h=7&lang=en",
+            "name=Dora&name=Dora",
+            &[],
+        );
+        assert_error(
+            response,
+            400,
+            "The data you submitted is not in the right format",
+        );
+    }
+
+    #[test]
+    fn tournament_closed() {
+        let api = MockApi::start();
+        let response =
+            Cgi::new(&api, "tournament_closed").get("u=club&t=winter&h=1&view=submit&lang=en");
+        assert_error(response, 403, "The tournament has finished");
+    }
+
+    #[test]
+    fn invalid_token() {
+        let api = MockApi::start();
+        let response = Cgi::new(&api, "invalid_token").post(
+            "u=club&t=summer&h=7&lang=en",
+            "token=1.00&name=Dora&score_m=3&score_cm=5",
+            &[],
+        );
+        assert_error(response, 403, "the form has been open for too long");
+        assert!(api.scores("club", "summer", 7).is_empty());
+    }
+
+    #[test]
+    fn too_many_requests() {
+        let api = MockApi::start();
+        let cgi = Cgi::new(&api, "too_many_requests").env("RATE_LIMIT", "1/600");
+        let first = submission(&cgi, "name=Dora&score_m=3&score_cm=5");
+        let second = submission(&cgi, "name=Emil&score_m=4&score_cm=5");
+
+        assert_eq!(cgi.post("u=club&t=summer&h=7", &first, &[]).status, 303);
+        let response = cgi.post("u=club&t=summer&h=7&lang=en", &second, &[]);
+        assert_eq!(
+            response
+                .header("Retry-After")
+                .map(|s| s.parse::<u64>().is_ok()),
+            Some(true)
+        );
+        assert_error(response, 429, "Too many scores have been submitted");
+    }
+
+    #[test]
+    fn a_rate_limited_form_can_be_submitted_again() {
+        let api = MockApi::start();
+        let cgi =
+            Cgi::new(&api, "a_rate_limited_form_can_be_submitted_again").env("RATE_LIMIT", "1/1");
+        let first = submission(&cgi, "name=Dora&score_m=3&score_cm=5");
+        let second = submission(&cgi, "name=Emil&score_m=4&score_cm=5");
+
+        assert_eq!(cgi.post("u=club&t=summer&h=7", &first, &[]).status, 303);
+        assert_eq!(cgi.post("u=club&t=summer&h=7", &second, &[]).status, 429);
+        thread::sleep(Duration::from_millis(2100));
+        assert_eq!(cgi.post("u=club&t=summer&h=7", &second, &[]).status, 303);
+        assert_eq!(api.scores("club", "summer", 7).len(), 2);
+    }
+
+    #[test]
+    fn edit_expired() {
+        let api = MockApi::start();
+        let response = Cgi::new(&api, "edit_expired").get("u=club&t=summer&h=7&view=edit&lang=en");
+        assert_error(response, 403, "can't be changed anymore");
+    }
+
+    #[test]
+    fn not_found() {
+        let demo = concat!(
+            "file://",
+            env!("CARGO_MANIFEST_DIR"),
+            "/../server/demo.json"
+        );
+        let response = Cgi::with_server(demo, "not_found").get("u=demo&t=winter&lang=en");
+        assert_error(response, 404, "doesn't exist");
+    }
+
+    #[test]
+    fn critical_server() {
+        let api = MockApi::start();
+        let response = Cgi::new(&api, "critical_server")
+            .env("RESOLUTION_CM", "0")
+            .get("u=club&t=summer&h=3&lang=en");
+        assert_error(response, 500, "this is a bug");
+    }
+
+    #[test]
+    fn invalid_json_is_critical() {
+        let api = MockApi::start();
+        api.fail_with(Failure::InvalidJson);
+        let response = Cgi::new(&api, "invalid_json_is_critical").get("u=club&lang=en");
+        assert_error(response, 500, "this is a bug");
+    }
+
+    #[test]
+    fn backend_connection() {
+        let api = MockApi::start();
+        // A port that was free a moment ago
+        let closed = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let response = Cgi::new(&api, "backend_connection")
+            .env("SERVER_URL", &format!("http://{closed}"))
+            .get("u=club&lang=en");
+        assert_eq!(response.header("Retry-After"), Some("60"));
+        assert_error(response, 503, "could not communicate with the API server");
+    }
+
+    #[test]
+    fn backend_status() {
+        let api = MockApi::start();
+        let response = Cgi::new(&api, "backend_status").get("u=nobody&lang=en");
+        assert_error(response, 502, "An error happened on the API server");
+    }
+
+    #[test]
+    fn in_the_format_asked_for() {
+        let api = MockApi::start();
+        let cgi = Cgi::new(&api, "errors_in_the_format_asked_for");
+
+        let response = cgi.get("u=nobody&format=json&lang=en");
+        assert_eq!(response.status, 502);
+        assert_eq!(response.header("Content-Type"), Some("application/json"));
+        let error: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(error["status"], 502);
+        let message = error["message"].as_str().unwrap();
+        assert!(message.starts_with("An error happened on the API server"));
+        assert!(!message.contains('<'), "{message}");
+
+        let response = cgi.get_with("u=nobody&t=summer&lang=en", &[("Accept", "text/csv")]);
+        assert_eq!(response.status, 502);
+        assert_eq!(
+            response.header("Content-Type"),
+            Some("text/plain; charset=utf-8")
+        );
+        assert!(!response.body.contains('<'), "{}", response.body);
+    }
+}
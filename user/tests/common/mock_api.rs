@@ -0,0 +1,237 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/**
+ * A stand-in for the BullseyeGolf API server
+ *
+ * Serves the routes the CGI script uses, see
+ * <https://github.com/itzgoldenleonard/BullseyeGolf-server/blob/main/openapi.yaml>
+ * - `GET /{user}`: the tournaments, without their holes
+ * - `GET /{user}/{tournament}`: the tournament with every hole
+ * - `GET /{user}/{tournament}/{hole}`: the hole
+ * - `POST /{user}/{tournament}/{hole}`: adds the score in the body, keeping the scores sorted
+ * - `DELETE /{user}/{tournament}/{hole}`: removes the score in the body. This one hasn't been
+ *   checked against the spec, it's what changing a score assumes the API server supports
+ *
+ * The data starts out as `tests/fixtures/api.json`, where `t_start` and `t_end` are seconds
+ * relative to when the server was started, so the tournaments stay active.
+ * Every test should start its own server, since submissions change the data.
+ */
+pub struct MockApi {
+    /// What `SERVER_URL` should be set to
+    pub url: String,
+    data: Arc<Mutex<Value>>,
+    failure: Arc<Mutex<Option<Failure>>>,
+    requests: Arc<AtomicUsize>,
+}
+
+/// Ways the server can misbehave, for testing how errors are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// Closes the connection before the whole body has been sent
+    TruncatedBody,
+    /// Responds with something that isn't JSON
+    InvalidJson,
+    /// Rejects the next `POST` with a server error, then works again
+    RejectNextPost,
+}
+
+impl MockApi {
+    pub fn start() -> Self {
+        let fixtures = include_str!("../fixtures/api.json");
+        let mut data: Value = serde_json::from_str(fixtures).expect("invalid fixtures");
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        for tournament in data.as_object_mut().unwrap().values_mut() {
+            for tournament in tournament.as_object_mut().unwrap().values_mut() {
+                for time in ["t_start", "t_end"] {
+                    tournament[time] = json!(now + tournament[time].as_i64().unwrap());
+                }
+            }
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let api = Self {
+            url,
+            data: Arc::new(Mutex::new(data)),
+            failure: Arc::new(Mutex::new(None)),
+            requests: Arc::new(AtomicUsize::new(0)),
+        };
+        let (data, failure, requests) =
+            (api.data.clone(), api.failure.clone(), api.requests.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                requests.fetch_add(1, Ordering::SeqCst);
+                let (data, failure) = (data.clone(), failure.clone());
+                thread::spawn(move || handle(stream, &data, &failure));
+            }
+        });
+        api
+    }
+
+    /// Makes every following response fail with `failure`
+    pub fn fail_with(&self, failure: Failure) {
+        *self.failure.lock().unwrap() = Some(failure);
+    }
+
+    /// How many requests the server has received
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+
+    /// The scores on a hole as they are on the server now
+    pub fn scores(&self, user: &str, tournament: &str, hole: u8) -> Vec<Value> {
+        let data = self.data.lock().unwrap();
+        find_hole(&data, user, tournament, hole)
+            .map(|h| h["scores"].as_array().unwrap().clone())
+            .expect("no such hole")
+    }
+}
+
+fn find_hole<'a>(data: &'a Value, user: &str, tournament: &str, hole: u8) -> Option<&'a Value> {
+    data.get(user)?
+        .get(tournament)?
+        .get("holes")?
+        .as_array()?
+        .iter()
+        .find(|h| h["hole_number"] == json!(hole))
+}
+
+fn handle(mut stream: TcpStream, data: &Mutex<Value>, failure: &Mutex<Option<Failure>>) {
+    let Some((method, path, body)) = read_request(&stream) else {
+        return;
+    };
+    let mut failure = failure.lock().unwrap();
+    let (status, body) = match *failure {
+        Some(Failure::InvalidJson) => (200, "<html>Not the API</html>".to_owned()),
+        Some(Failure::RejectNextPost) if method == "POST" => {
+            *failure = None;
+            (500, json!({ "detail": "Rejected" }).to_string())
+        }
+        failure => {
+            let (status, value) = route(&method, &path, &body, &mut data.lock().unwrap());
+            let body = value.to_string();
+            if failure == Some(Failure::TruncatedBody) {
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len() + 100,
+                    &body[..body.len() / 2]
+                );
+                return;
+            }
+            (status, body)
+        }
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {status} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+}
+
+fn read_request(stream: &TcpStream) -> Option<(String, String, Vec<u8>)> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_owned();
+    let path = parts.next()?.to_owned();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).ok()?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().ok()?;
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok()?;
+    Some((method, path, body))
+}
+
+fn route(method: &str, path: &str, body: &[u8], data: &mut Value) -> (u16, Value) {
+    let not_found = (404, json!({ "detail": "Not found" }));
+    let path: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+    let Some(user) = path.first().and_then(|u| data.get_mut(*u)) else {
+        return not_found;
+    };
+
+    match (method, &path[1..]) {
+        ("GET", []) => {
+            let tournaments = user.as_object().unwrap().iter().map(|(id, t)| {
+                json!({
+                    "tournament_id": id,
+                    "tournament_name": t["tournament_name"],
+                    "active": t["active"],
+                    "t_start": t["t_start"],
+                    "t_end": t["t_end"],
+                })
+            });
+            (200, Value::Array(tournaments.collect()))
+        }
+        ("GET", [tournament]) => match user.get(*tournament) {
+            Some(t) => (
+                200,
+                json!({
+                    "tournament_name": t["tournament_name"],
+                    "tournament_sponsor": t["tournament_sponsor"],
+                    "holes": t["holes"],
+                }),
+            ),
+            None => not_found,
+        },
+        (method, [tournament, hole]) => {
+            let hole = user
+                .get_mut(*tournament)
+                .and_then(|t| t["holes"].as_array_mut())
+                .and_then(|holes| {
+                    holes
+                        .iter_mut()
+                        .find(|h| h["hole_number"].as_u64() == hole.parse().ok())
+                });
+            let Some(hole) = hole else {
+                return not_found;
+            };
+            if method == "GET" {
+                return (200, hole.clone());
+            }
+
+            let Ok(score) = serde_json::from_slice::<Value>(body) else {
+                return (422, json!({ "detail": "Invalid score" }));
+            };
+            let scores = hole["scores"].as_array_mut().unwrap();
+            match method {
+                "POST" => {
+                    scores.push(score.clone());
+                    scores.sort_by(|a, b| {
+                        let distance = |s: &Value| s["player_score"].as_f64().unwrap();
+                        distance(a).total_cmp(&distance(b))
+                    });
+                    (200, score)
+                }
+                "DELETE" => match scores.iter().position(|s| *s == score) {
+                    Some(i) => (200, scores.remove(i)),
+                    None => not_found,
+                },
+                _ => (405, json!({ "detail": "Method not allowed" })),
+            }
+        }
+        _ => not_found,
+    }
+}
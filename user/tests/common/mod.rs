@@ -0,0 +1,164 @@
+//! Shared by the integration tests, which drive the CGI script against [`MockApi`]
+
+// Every test file only uses some of the helpers
+#![allow(dead_code)]
+
+pub mod mock_api;
+
+pub use mock_api::MockApi;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/**
+ * A CGI request to the script
+ *
+ * Every request gets a fresh environment with `SERVER_URL` pointing at the mock API server and a
+ * `STATE_DIR` for the test, named after it so tests running in parallel don't share state.
+ * The requests carry the same `csrf` cookie, like a browser that has been shown a form before
+ */
+pub struct Cgi {
+    env: Vec<(String, String)>,
+    csrf_cookie: Option<&'static str>,
+}
+
+/// The `csrf` cookie [`Cgi`] sends by default
+pub const CSRF_COOKIE: &str = "csrf=0123456789abcdef0123456789abcdef";
+
+impl Cgi {
+    pub fn new(api: &MockApi, test: &str) -> Self {
+        Self::with_server(&api.url, test)
+    }
+
+    /// With `SERVER_URL` set to `server` instead of a [`MockApi`]
+    pub fn with_server(server: &str, test: &str) -> Self {
+        let state_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(test);
+        let _ = fs::remove_dir_all(&state_dir);
+        Self {
+            env: vec![
+                ("GATEWAY_INTERFACE".into(), "CGI/1.1".into()),
+                ("SERVER_URL".into(), server.into()),
+                ("STATE_DIR".into(), state_dir.display().to_string()),
+                ("REMOTE_ADDR".into(), "192.0.2.1".into()),
+            ],
+            csrf_cookie: Some(CSRF_COOKIE),
+        }
+    }
+
+    /// Sends `cookie` as the `csrf` cookie from now on, or none
+    pub fn csrf_cookie(mut self, cookie: Option<&'static str>) -> Self {
+        self.csrf_cookie = cookie;
+        self
+    }
+
+    /// Sets an environment variable for every following request
+    pub fn env(mut self, name: &str, value: &str) -> Self {
+        self.env.retain(|(n, _)| n != name);
+        self.env.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn get(&self, query: &str) -> CgiResponse {
+        self.run("GET", query, &[], b"")
+    }
+
+    /// Submits `form` like a browser would, with `headers` as `(name, value)`
+    pub fn post(&self, query: &str, form: &str, headers: &[(&str, &str)]) -> CgiResponse {
+        self.run("POST", query, headers, form.as_bytes())
+    }
+
+    /// A GET with extra `headers`, e.g. `Cookie`
+    pub fn get_with(&self, query: &str, headers: &[(&str, &str)]) -> CgiResponse {
+        self.run("GET", query, headers, b"")
+    }
+
+    fn run(&self, method: &str, query: &str, headers: &[(&str, &str)], body: &[u8]) -> CgiResponse {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_bullseyegolf-frontend-light-user"));
+        command
+            .env_clear()
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .env("REQUEST_METHOD", method)
+            .env("QUERY_STRING", query)
+            .env("CONTENT_LENGTH", body.len().to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped());
+        let mut cookies: Vec<&str> = self.csrf_cookie.into_iter().collect();
+        for (name, value) in headers {
+            if name.eq_ignore_ascii_case("cookie") {
+                cookies.push(value);
+                continue;
+            }
+            let name = format!("HTTP_{}", name.to_uppercase().replace('-', "_"));
+            command.env(name, value);
+        }
+        if !cookies.is_empty() {
+            command.env("HTTP_COOKIE", cookies.join("; "));
+        }
+
+        let mut child = command.spawn().expect("could not start the CGI script");
+        child.stdin.take().unwrap().write_all(body).unwrap();
+        let output = child.wait_with_output().unwrap();
+        CgiResponse::parse(&String::from_utf8(output.stdout).expect("not UTF-8"))
+    }
+}
+
+/// What the CGI script wrote to stdout
+#[derive(Debug)]
+pub struct CgiResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl CgiResponse {
+    fn parse(output: &str) -> Self {
+        let (head, body) = output
+            .split_once("\r\n\r\n")
+            .expect("no end of the headers");
+        let mut headers: Vec<(String, String)> = head
+            .split("\r\n")
+            .filter_map(|h| h.split_once(": "))
+            .map(|(n, v)| (n.to_owned(), v.to_owned()))
+            .collect();
+        let status = headers
+            .iter()
+            .position(|(n, _)| n == "Status")
+            .map(|i| headers.remove(i).1)
+            .expect("no Status header");
+        Self {
+            status: status[..3].parse().unwrap(),
+            headers,
+            body: body.to_owned(),
+        }
+    }
+
+    /// The first header called `name`
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The `name=value` part of the cookie called `name` that the response sets
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .filter(|(n, _)| n == "Set-Cookie")
+            .filter_map(|(_, v)| v.split(';').next())
+            .find(|c| c.starts_with(&format!("{name}=")))
+    }
+
+    /// The value of the hidden form field called `name`
+    pub fn hidden_input(&self, name: &str) -> &str {
+        hidden_input(&self.body, name)
+    }
+}
+
+/// The value of the hidden form field called `name` in `html`
+pub fn hidden_input<'a>(html: &'a str, name: &str) -> &'a str {
+    let start = format!("name=\"{name}\" type=\"hidden\" value=\"");
+    let value = html.split_once(&start).expect("no such field").1;
+    &value[..value.find('"').unwrap()]
+}
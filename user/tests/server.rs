@@ -0,0 +1,166 @@
+//! Drives the standalone HTTP server started with `--listen`
+
+mod common;
+
+use common::{hidden_input, MockApi, CSRF_COOKIE};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::process::{Child, ChildStderr, Command, Stdio};
+
+/// The server, running until it's dropped
+struct Server {
+    child: Child,
+    addr: String,
+    state_dir: PathBuf,
+    /// Kept open, so the server doesn't fail writing to it
+    _stderr: BufReader<ChildStderr>,
+}
+
+impl Server {
+    fn start(test: &str) -> Self {
+        Self::with_env(test, &[])
+    }
+
+    /// With the environment variables in `env` too, they override the defaults
+    fn with_env(test: &str, env: &[(&str, &str)]) -> Self {
+        let manifest = env!("CARGO_MANIFEST_DIR");
+        let state_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(test);
+        let _ = fs::remove_dir_all(&state_dir);
+        fs::create_dir_all(&state_dir).unwrap();
+        // A copy, since scores can be submitted to it
+        let demo = state_dir.join("demo.json");
+        fs::copy(format!("{manifest}/../server/demo.json"), &demo).unwrap();
+        let mut child = Command::new(env!("CARGO_BIN_EXE_bullseyegolf-frontend-light-user"))
+            .args(["--listen", "127.0.0.1:0", "--document-root"])
+            .arg(format!("{manifest}/../server/document-root"))
+            .env_clear()
+            .env("SERVER_URL", format!("file://{}", demo.display()))
+            .env("STATE_DIR", &state_dir)
+            .envs(env.iter().copied())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("could not start the server");
+
+        let mut line = String::new();
+        let mut stderr = BufReader::new(child.stderr.take().unwrap());
+        stderr.read_line(&mut line).unwrap();
+        let addr = line
+            .trim()
+            .strip_prefix("Listening on http://")
+            .unwrap_or_else(|| panic!("unexpected output: {line}"))
+            .to_owned();
+        Self {
+            child,
+            addr,
+            state_dir,
+            _stderr: stderr,
+        }
+    }
+
+    /// Sends `request` as it is and returns the status code of the response
+    fn send(&self, request: &[u8]) -> u16 {
+        let response = self.fetch(request);
+        response
+            .split_whitespace()
+            .nth(1)
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(|| panic!("unexpected response: {response}"))
+    }
+
+    /// Sends `request` as it is and returns the whole response
+    fn fetch(&self, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(&self.addr).unwrap();
+        stream.write_all(request).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn serves_pages() {
+    let server = Server::start("server_serves_pages");
+    assert_eq!(server.send(b"GET /u?u=demo HTTP/1.1\r\n\r\n"), 200);
+    assert_eq!(server.send(b"GET /user.css HTTP/1.1\r\n\r\n"), 200);
+    assert_eq!(server.send(b"GET /nothing HTTP/1.1\r\n\r\n"), 404);
+}
+
+#[test]
+fn keeps_state_in_the_state_dir() {
+    let server = Server::start("server_keeps_state_in_the_state_dir");
+    let cookie = format!("Cookie: {CSRF_COOKIE}\r\n");
+    let form = server.fetch(
+        format!("GET /u?u=demo&t=sommer&h=3&view=submit HTTP/1.1\r\n{cookie}\r\n").as_bytes(),
+    );
+    let token = hidden_input(&form, "token");
+
+    let body = format!("token={token}&name=Dora&score_m=3&score_cm=5");
+    let request = format!(
+        "POST /u?u=demo&t=sommer&h=3 HTTP/1.1\r\n{cookie}Content-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    assert_eq!(server.send(request.as_bytes()), 303);
+    // Shared with any other process using the same directory, not only kept in memory
+    assert!(server.state_dir.join("edits.json").exists());
+    assert!(server.state_dir.join("rate_limit.json").exists());
+}
+
+#[test]
+fn caches_responses_in_memory() {
+    let api = MockApi::start();
+    let server = Server::with_env(
+        "server_caches_responses_in_memory",
+        &[("SERVER_URL", &api.url), ("CACHE_SECONDS", "60")],
+    );
+    assert_eq!(
+        server.send(b"GET /u?u=club&t=summer&h=7 HTTP/1.1\r\n\r\n"),
+        200
+    );
+    let requests = api.requests();
+    assert_eq!(
+        server.send(b"GET /u?u=club&t=summer&h=7 HTTP/1.1\r\n\r\n"),
+        200
+    );
+    assert_eq!(api.requests(), requests);
+    assert!(!server.state_dir.join("api_cache").exists());
+}
+
+#[test]
+fn rejects_bodies_that_are_too_large() {
+    let server = Server::start("server_rejects_bodies_that_are_too_large");
+    let request = b"POST /u?u=demo&t=sommer&h=3 HTTP/1.1\r\nContent-Length: 100000\r\n\r\n";
+    assert_eq!(server.send(request), 413);
+}
+
+#[test]
+fn rejects_lines_that_are_too_long() {
+    let server = Server::start("server_rejects_lines_that_are_too_long");
+    let request = format!(
+        "GET /u?u=demo HTTP/1.1\r\nCookie: {}\r\n\r\n",
+        "a".repeat(10_000)
+    );
+    assert_eq!(server.send(request.as_bytes()), 400);
+}
+
+#[test]
+fn refuses_to_start_with_invalid_settings() {
+    let output = Command::new(env!("CARGO_BIN_EXE_bullseyegolf-frontend-light-user"))
+        .args(["--listen", "127.0.0.1:0"])
+        .env_clear()
+        .env("SERVER_URL", "http://127.0.0.1:1")
+        .env("RESOLUTION_CM", "0")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("RESOLUTION_CM"), "{stderr}");
+}
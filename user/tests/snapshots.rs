@@ -0,0 +1,113 @@
+//! Compares the rendered pages with the golden files in `tests/snapshots`
+//!
+//! Run with `UPDATE_SNAPSHOTS=1` to write the golden files from the current output instead, and
+//! review the changes with `git diff` before committing them.
+
+mod common;
+
+use common::{Cgi, MockApi};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/**
+ * Fails unless `html` is the same as the golden file called `name`
+ *
+ * The HTML is stored with every tag on its own line, so the diffs are readable
+ */
+fn assert_snapshot(name: &str, html: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.html"));
+    let actual = html.replace("><", ">\n<") + "\n";
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some_and(|v| v == "1") {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let Ok(expected) = fs::read_to_string(&path) else {
+        panic!(
+            "{} doesn't exist, run with UPDATE_SNAPSHOTS=1 to create it",
+            path.display()
+        );
+    };
+    if expected != actual {
+        let (line, (expected, actual)) = expected
+            .lines()
+            .chain([""].into_iter().cycle())
+            .zip(actual.lines().chain([""].into_iter().cycle()))
+            .enumerate()
+            .find(|(_, (e, a))| e != a)
+            .unwrap();
+        panic!(
+            "{} differs from line {}:\nexpected: {expected}\nactual:   {actual}\n\
+             Run with UPDATE_SNAPSHOTS=1 if the change is intended",
+            path.display(),
+            line + 1
+        );
+    }
+}
+
+/// The HTML page for `query`
+fn page(test: &str, query: &str) -> String {
+    let api = MockApi::start();
+    let response = Cgi::new(&api, test).get(query);
+    assert_eq!(response.status, 200, "{}", response.body);
+    response.body
+}
+
+#[test]
+fn select_tournament_page() {
+    let html = page("snapshot_select_tournament_page", "u=club");
+    assert_snapshot("select_tournament_page", &html);
+}
+
+#[test]
+fn select_tournament_page_without_tournaments() {
+    let html = page(
+        "snapshot_select_tournament_page_without_tournaments",
+        "u=empty&lang=en",
+    );
+    assert_snapshot("select_tournament_page_without_tournaments", &html);
+}
+
+#[test]
+fn select_hole_page() {
+    let html = page("snapshot_select_hole_page", "u=club&t=summer");
+    assert_snapshot("select_hole_page", &html);
+}
+
+#[test]
+fn view_hole_page() {
+    let html = page("snapshot_view_hole_page", "u=club&t=summer&h=3");
+    assert_snapshot("view_hole_page", &html);
+}
+
+#[test]
+fn view_hole_page_in_english_and_feet() {
+    let html = page(
+        "snapshot_view_hole_page_in_english_and_feet",
+        "u=club&t=summer&h=3&lang=en&units=ft",
+    );
+    assert_snapshot("view_hole_page_in_english_and_feet", &html);
+}
+
+#[test]
+fn view_hole_page_without_scores() {
+    let html = page(
+        "snapshot_view_hole_page_without_scores",
+        "u=club&t=summer&h=7",
+    );
+    assert_snapshot("view_hole_page_without_scores", &html);
+}
+
+#[test]
+fn view_hole_page_of_finished_tournament() {
+    let html = page(
+        "snapshot_view_hole_page_of_finished_tournament",
+        "u=club&t=winter&h=1",
+    );
+    assert_snapshot("view_hole_page_of_finished_tournament", &html);
+}